@@ -1,210 +1,183 @@
-use serde::{Deserialize, Serialize};
+use std::{env, fs, path::Path};
 
-use crate::buildkite::BuildData;
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde_json::{json, Value};
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct AdaptiveCardData {
-    #[serde(rename = "type")]
-    data_type: String,
-    attachments: Vec<Attachment>,
-}
+use crate::buildkite::{AgentConnectionState, BuildState, BuildkiteEvent, JobState};
+use crate::template;
 
-impl From<BuildData> for AdaptiveCardData {
-    fn from(data: BuildData) -> Self {
-        let sender_name: &String = &data.sender_name;
+pub(crate) use lessons::builder::AdaptiveCardData;
 
-        let adaptive_card_data = AdaptiveCardData {
-            data_type: "message".to_string(),
-            attachments: vec![Attachment {
-                content_type: "application/vnd.microsoft.card.adaptive".to_string(),
-                content_url: "none".to_string(),
-                content: Content {
-                    content_type: "AdaptiveCard".to_string(),
-                    schema: "http://adaptivecards.io/schemas/adaptive-card.json".to_string(),
-                    version: "1.5".to_string(),
-                    body: vec![
-                        BodyItem::TextBlock(TextBlock {
-                            size: Some("medium".to_string()),
-                            weight: "Bolder".to_string(),
-                            text: format!(
-                                "{} #{} {:?}",
-                                data.pipeline.name, data.build.number, data.build.state
-                            ),
-                            color: "Good".to_string(),
-                            wrap: None,
-                            ..Default::default()
-                        }),
-                        BodyItem::ColumnSet(ColumnSet {
-                            columns: vec![
-                                Column {
-                                    column_type: "Column".to_string(),
-                                    items: vec![ColumnItem::Image(Image {
-                                        style: "person".to_string(),
-                                        url: data.creator_avatar,
-                                        alt_text: sender_name.to_string(),
-                                        size: "small".to_string(),
-                                    })],
-                                    width: "auto".to_string(),
-                                },
-                                Column {
-                                    column_type: "Column".to_string(),
-                                    items: vec![
-                                        ColumnItem::TextBlock(TextBlock {
-                                            weight: "Bolder".to_string(),
-                                            text: sender_name.to_string(),
-                                            wrap: Some(true),
-                                            ..Default::default()
-                                        }),
-                                        ColumnItem::TextBlock(TextBlock {
-                                            text: format!("Created {}", data.build.created_at),
-                                            is_subtle: Some(true),
-                                            spacing: "none".to_string(),
-                                            wrap: Some(true),
-                                            ..Default::default()
-                                        }),
-                                    ],
-                                    width: "stretch".to_string(),
-                                },
-                            ],
-                        }),
-                        BodyItem::TextBlock(TextBlock {
-                            text: format!("Repository: {}", data.pipeline.repository),
-                            is_subtle: Some(true),
-                            font_type: "Monospace".to_string(),
-                            size: Some("small".to_string()),
-                            wrap: Some(true),
-                            ..Default::default()
-                        }),
-                        BodyItem::TextBlock(TextBlock {
-                            text: format!("Commit: {}", data.build.commit),
-                            is_subtle: Some(true),
-                            font_type: "Monospace".to_string(),
-                            size: Some("small".to_string()),
-                            wrap: Some(true),
-                            ..Default::default()
-                        }),
-                    ],
-                    actions: vec![Action {
-                        action_type: "Action.OpenUrl".to_string(),
-                        title: "View in Buildkite".to_string(),
-                        url: format!("{}/builds/{}", data.pipeline.web_url, data.build.number),
-                    }],
-                },
-            }],
-        };
-        adaptive_card_data
-    }
+// Expands the named card template against `data`, preferring one in CARD_TEMPLATE_DIR.
+pub(crate) fn from_template(name: &str, data: &Value) -> AdaptiveCardData {
+    let dir = env::var_os("CARD_TEMPLATE_DIR");
+    from_template_in(dir.as_deref().map(Path::new), name, data)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Attachment {
-    #[serde(rename = "contentType")]
-    content_type: String,
-    #[serde(rename = "contentUrl")]
-    content_url: String,
-    content: Content,
+// Expands the named card template against `data`, preferring one in `dir`.  A custom template
+// that can't be read or expanded is logged and the built-in layout used in its place.
+pub(crate) fn from_template_in(dir: Option<&Path>, name: &str, data: &Value) -> AdaptiveCardData {
+    if let Some(custom) = dir.and_then(|dir| custom_template(dir, name)) {
+        match custom.and_then(|card| template::expand(&card, data).map_err(|e| e.to_string())) {
+            Ok(card) => return AdaptiveCardData::raw(card),
+            Err(err) => println!("Card template {name}: {err}, using the built-in layout"),
+        }
+    }
+    match template::expand(&builtin_template(name), data) {
+        Ok(card) => AdaptiveCardData::raw(card),
+        Err(err) => Card::new()
+            .title(
+                format!("Could not render the {name} card"),
+                Color::Attention,
+            )
+            .text(err.to_string())
+            .into(),
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Content {
-    #[serde(rename = "type")]
-    content_type: String,
-    #[serde(rename = "$schema")]
-    schema: String,
-    version: String,
-    body: Vec<BodyItem>,
-    actions: Vec<Action>,
-}
+// Layouts for the built-in sources, in the Adaptive Card Templating language.  A file of the
+// same name in CARD_TEMPLATE_DIR, e.g. buildkite-build.json, takes precedence, so a layout
+// can be changed without a rebuild.
+const TEMPLATES: [(&str, &str); 7] = [
+    (
+        "buildkite-build",
+        include_str!("../templates/buildkite-build.json"),
+    ),
+    (
+        "buildkite-job",
+        include_str!("../templates/buildkite-job.json"),
+    ),
+    (
+        "buildkite-agent",
+        include_str!("../templates/buildkite-agent.json"),
+    ),
+    (
+        "buildkite-agent-blocked",
+        include_str!("../templates/buildkite-agent-blocked.json"),
+    ),
+    (
+        "buildkite-cluster-token",
+        include_str!("../templates/buildkite-cluster-token.json"),
+    ),
+    (
+        "buildkite-ping",
+        include_str!("../templates/buildkite-ping.json"),
+    ),
+    ("dms", include_str!("../templates/dms.json")),
+];
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type")]
-enum BodyItem {
-    TextBlock(TextBlock),
-    ColumnSet(ColumnSet),
+fn builtin_template(name: &str) -> Value {
+    let (_, source) = TEMPLATES
+        .iter()
+        .find(|(template, _)| *template == name)
+        .expect("no built-in template by that name");
+    serde_json::from_str(source).expect("built-in templates are valid JSON")
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TextBlock {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    size: Option<String>,
-    #[serde(skip_serializing_if = "weight_is_normal")]
-    weight: String,
-    #[serde(skip_serializing_if = "color_is_blank")]
-    color: String,
-    text: String,
-    #[serde(skip_serializing_if = "spacing_is_blank")]
-    spacing: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(rename = "isSubtle")]
-    is_subtle: Option<bool>,
-    #[serde(skip_serializing_if = "font_is_set")]
-    #[serde(rename = "fontType")]
-    font_type: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    wrap: Option<bool>,
+// Loads a card template from a JSON file.
+pub(crate) fn load_template(path: &Path) -> Result<Value, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    serde_json::from_str(&source).map_err(|err| format!("{}: {err}", path.display()))
 }
 
-fn weight_is_normal(weight: &String) -> bool {
-    weight == "normal"
-}
-fn font_is_set(font_type: &String) -> bool {
-    font_type == "default"
-}
-fn spacing_is_blank(spacing: &String) -> bool {
-    spacing == ""
-}
-fn color_is_blank(color: &String) -> bool {
-    color == ""
+// None when there is no custom template for `name`, so the built-in one applies.
+fn custom_template(dir: &Path, name: &str) -> Option<Result<Value, String>> {
+    let path = dir.join(format!("{name}.json"));
+    path.exists().then(|| load_template(&path))
 }
 
-impl Default for TextBlock {
-    fn default() -> Self {
-        TextBlock {
-            size: None,
-            weight: "normal".to_string(),
-            color: "".to_string(),
-            text: "".to_string(),
-            spacing: "".to_string(),
-            is_subtle: Some(false),
-            font_type: "default".to_string(),
-            wrap: Some(false),
-        }
+// Template data for an event: its payload as received, plus a `view` object holding what
+// needs Rust to work out, like state names and colors.
+pub(crate) fn template_data(mut data: Value, view: Value) -> Value {
+    if let Some(fields) = data.as_object_mut() {
+        fields.insert("view".to_string(), view);
     }
+    data
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct ColumnSet {
-    columns: Vec<Column>,
+fn build_state_color(state: &BuildState) -> &'static str {
+    match state {
+        BuildState::Passed => "Good",
+        BuildState::Failing | BuildState::Failed => "Attention",
+        BuildState::Blocked | BuildState::Canceling | BuildState::Canceled => "Warning",
+        _ => "Accent",
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Column {
-    #[serde(rename = "type")]
-    column_type: String,
-    items: Vec<ColumnItem>,
-    width: String,
+fn job_state_color(state: &JobState) -> &'static str {
+    match state {
+        JobState::Passed => "Good",
+        JobState::Failed
+        | JobState::TimedOut
+        | JobState::Broken
+        | JobState::Expired
+        | JobState::WaitingFailed
+        | JobState::BlockedFailed
+        | JobState::UnblockedFailed => "Attention",
+        JobState::Blocked | JobState::Canceling | JobState::Canceled | JobState::TimingOut => {
+            "Warning"
+        }
+        _ => "Accent",
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type")]
-enum ColumnItem {
-    Image(Image),
-    TextBlock(TextBlock),
+fn agent_state_color(state: &AgentConnectionState) -> &'static str {
+    match state {
+        AgentConnectionState::Connected => "Good",
+        AgentConnectionState::Lost => "Attention",
+        _ => "Warning",
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Image {
-    style: String,
-    url: String,
-    #[serde(rename = "altText")]
-    alt_text: String,
-    size: String,
+// The card for a Buildkite event, laid out from `payload`, the webhook body it was parsed from,
+// so templates can use the fields BuildkiteEvent doesn't model too.
+pub(crate) fn buildkite_card(event: &BuildkiteEvent, payload: Value) -> AdaptiveCardData {
+    let (name, view) = match event {
+        BuildkiteEvent::BuildScheduled(data)
+        | BuildkiteEvent::BuildRunning(data)
+        | BuildkiteEvent::BuildFailing(data)
+        | BuildkiteEvent::BuildFinished(data)
+        | BuildkiteEvent::BuildSkipped(data) => {
+            let view = json!({
+                "state": data.build.state.label(),
+                "color": build_state_color(&data.build.state),
+            });
+            ("buildkite-build", view)
+        }
+        BuildkiteEvent::JobScheduled(data)
+        | BuildkiteEvent::JobStarted(data)
+        | BuildkiteEvent::JobFinished(data)
+        | BuildkiteEvent::JobActivated(data) => {
+            let view = json!({
+                "state": data.job.state.label(),
+                "color": job_state_color(&data.job.state),
+            });
+            ("buildkite-job", view)
+        }
+        BuildkiteEvent::AgentConnected(data)
+        | BuildkiteEvent::AgentLost(data)
+        | BuildkiteEvent::AgentDisconnected(data)
+        | BuildkiteEvent::AgentStopping(data)
+        | BuildkiteEvent::AgentStopped(data) => {
+            let view = json!({
+                "state": data.agent.connection_state.label(),
+                "color": agent_state_color(&data.agent.connection_state),
+            });
+            ("buildkite-agent", view)
+        }
+        BuildkiteEvent::AgentBlocked(_) => ("buildkite-agent-blocked", json!({})),
+        BuildkiteEvent::ClusterTokenRegistrationBlocked(_) => {
+            ("buildkite-cluster-token", json!({}))
+        }
+        BuildkiteEvent::Ping(_) => ("buildkite-ping", json!({})),
+    };
+    from_template(name, &template_data(payload, view))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Action {
-    #[serde(rename = "type")]
-    action_type: String,
-    title: String,
-    url: String,
+impl From<BuildkiteEvent> for AdaptiveCardData {
+    fn from(event: BuildkiteEvent) -> Self {
+        let payload = serde_json::to_value(&event).unwrap_or_default();
+        buildkite_card(&event, payload)
+    }
 }
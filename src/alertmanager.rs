@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+
+// Prometheus Alertmanager webhook_config payload, one per alert group, see
+// https://prometheus.io/docs/alerting/latest/configuration/#webhook_config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertmanagerData {
+    pub version: Option<String>,
+    pub group_key: Option<String>,
+    #[serde(default)]
+    pub truncated_alerts: u64,
+    pub status: AlertStatus,
+    pub receiver: String,
+    #[serde(default)]
+    pub group_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(rename = "externalURL")]
+    pub external_url: Option<String>,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub status: AlertStatus,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+    #[serde(rename = "generatorURL")]
+    pub generator_url: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+// Label that can name the Teams channel when the request has no `channel` query parameter.
+pub const CHANNEL_LABEL: &str = "teams_channel";
+
+// Most alerts to list individually before summarising the rest.
+const MAX_LISTED_ALERTS: usize = 10;
+
+impl AlertmanagerData {
+    pub fn channel_label(&self) -> Option<&String> {
+        self.group_labels
+            .get(CHANNEL_LABEL)
+            .or(self.common_labels.get(CHANNEL_LABEL))
+    }
+
+    // Alertmanager UI link for a new silence pre-filled with the group's common labels.
+    fn silence_url(&self) -> Option<String> {
+        let external_url = self.external_url.as_ref()?;
+        let matchers: Vec<String> = self
+            .common_labels
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{value}\""))
+            .collect();
+        Some(format!(
+            "{}/#/silences/new?filter={}",
+            external_url.trim_end_matches('/'),
+            percent_encode(&format!("{{{}}}", matchers.join(",")))
+        ))
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn alert_line(alert: &Alert) -> String {
+    let marker = match alert.status {
+        AlertStatus::Firing => "FIRING",
+        AlertStatus::Resolved => "RESOLVED",
+    };
+    let summary = alert
+        .annotations
+        .get("summary")
+        .or(alert.annotations.get("description"))
+        .or(alert.labels.get("alertname"))
+        .map_or("alert", |s| s.as_str());
+    match alert.labels.get("instance") {
+        Some(instance) => format!("**{marker}** {summary} ({instance})"),
+        None => format!("**{marker}** {summary}"),
+    }
+}
+
+impl From<AlertmanagerData> for AdaptiveCardData {
+    fn from(data: AlertmanagerData) -> Self {
+        let firing = data
+            .alerts
+            .iter()
+            .filter(|a| a.status == AlertStatus::Firing)
+            .count();
+        let (status, color) = match data.status {
+            AlertStatus::Firing => (format!("FIRING:{firing}"), Color::Attention),
+            AlertStatus::Resolved => ("RESOLVED".to_string(), Color::Good),
+        };
+        let name = data
+            .common_labels
+            .get("alertname")
+            .or(data.group_labels.get("alertname"))
+            .map_or(data.receiver.as_str(), |s| s.as_str());
+        let mut card = Card::new().title(format!("[{status}] {name}"), color);
+        if let Some(summary) = data
+            .common_annotations
+            .get("summary")
+            .or(data.common_annotations.get("description"))
+        {
+            card = card.text(summary.clone());
+        }
+        if !data.common_labels.is_empty() {
+            card = card.facts(
+                data.common_labels
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            );
+        }
+        let mut lines: Vec<String> = data
+            .alerts
+            .iter()
+            .take(MAX_LISTED_ALERTS)
+            .map(alert_line)
+            .collect();
+        let hidden =
+            data.alerts.len().saturating_sub(MAX_LISTED_ALERTS) as u64 + data.truncated_alerts;
+        if hidden > 0 {
+            lines.push(format!("...and {hidden} more"));
+        }
+        card = card.text(lines.join("\n\n"));
+
+        if let Some(url) = data.alerts.iter().find_map(|a| a.generator_url.clone()) {
+            card = card.open_url("View source", url);
+        }
+        if data.status == AlertStatus::Firing {
+            if let Some(url) = data.silence_url() {
+                card = card.open_url("Silence", url);
+            }
+        }
+        if let Some(url) = data.external_url {
+            card = card.open_url("Open Alertmanager", url);
+        }
+        card.into()
+    }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::card::{
+    Action, ActionProps, AdaptiveCard, Color, Column, ColumnSet, Element, ElementProps, Fact,
+    FactSet, FontSize, FontType, FontWeight, Image, ImageSize, ImageStyle, OpenUrl, Spacing,
+    TextBlock, Width,
+};
+
+const SCHEMA: &str = "http://adaptivecards.io/schemas/adaptive-card.json";
+
+/// Fluent builder for the cards the adapter sends: a colored title, then bylines, text, details
+/// and facts in the order they're added, and link buttons at the bottom.
+///
+/// ```
+/// use lessons::builder::{AdaptiveCardData, Card};
+/// use lessons::card::Color;
+///
+/// let message: AdaptiveCardData = Card::new()
+///     .title("app #42 failed", Color::Attention)
+///     .detail("Commit: abc123")
+///     .facts([("Branch", "main")])
+///     .open_url("View build", "https://buildkite.com/acme/app/builds/42")
+///     .into();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Card {
+    body: Vec<Element>,
+    actions: Vec<Action>,
+}
+
+impl Card {
+    /// Empty card; what it shows is added with the methods below.
+    pub fn new() -> Self {
+        Card::default()
+    }
+
+    /// Bold headline at the top of the card.
+    pub fn title(self, text: impl Into<String>, color: Color) -> Self {
+        self.element(Element::TextBlock(TextBlock {
+            text: text.into(),
+            size: Some(FontSize::Medium),
+            weight: Some(FontWeight::Bolder),
+            color: Some(color),
+            ..Default::default()
+        }))
+    }
+
+    /// Avatar (when there is one) next to a bold name and a subtle second line.
+    pub fn byline(self, name: &str, avatar_url: Option<&str>, subtitle: impl Into<String>) -> Self {
+        let mut columns = vec![];
+        if let Some(url) = avatar_url {
+            columns.push(column(
+                "auto",
+                vec![Element::Image(Image {
+                    style: Some(ImageStyle::Person),
+                    url: url.to_string(),
+                    alt_text: Some(name.to_string()),
+                    size: Some(ImageSize::Small),
+                    ..Default::default()
+                })],
+            ));
+        }
+        columns.push(column(
+            "stretch",
+            vec![
+                Element::TextBlock(TextBlock {
+                    weight: Some(FontWeight::Bolder),
+                    text: name.to_string(),
+                    wrap: Some(true),
+                    ..Default::default()
+                }),
+                Element::TextBlock(TextBlock {
+                    text: subtitle.into(),
+                    is_subtle: Some(true),
+                    wrap: Some(true),
+                    props: ElementProps {
+                        spacing: Some(Spacing::None),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+            ],
+        ));
+        self.column_set(columns)
+    }
+
+    /// Plain wrapped paragraph, for free-form text like notes or messages.
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.element(Element::TextBlock(TextBlock {
+            text: text.into(),
+            wrap: Some(true),
+            ..Default::default()
+        }))
+    }
+
+    /// Small subtle monospace line, for repository/commit style details.
+    pub fn detail(self, text: impl Into<String>) -> Self {
+        self.element(Element::TextBlock(TextBlock {
+            text: text.into(),
+            is_subtle: Some(true),
+            font_type: Some(FontType::Monospace),
+            size: Some(FontSize::Small),
+            wrap: Some(true),
+            ..Default::default()
+        }))
+    }
+
+    /// Two-column title/value list; an empty list adds nothing.
+    pub fn facts<T: Into<String>, V: Into<String>>(
+        self,
+        facts: impl IntoIterator<Item = (T, V)>,
+    ) -> Self {
+        let facts: Vec<Fact> = facts
+            .into_iter()
+            .map(|(title, value)| Fact {
+                title: title.into(),
+                value: value.into(),
+                ..Default::default()
+            })
+            .collect();
+        if facts.is_empty() {
+            return self;
+        }
+        self.element(Element::FactSet(FactSet {
+            facts,
+            ..Default::default()
+        }))
+    }
+
+    /// Full-width picture, e.g. a graph snapshot.
+    pub fn image(self, url: impl Into<String>, alt_text: impl Into<String>) -> Self {
+        self.element(Element::Image(Image {
+            style: Some(ImageStyle::Default),
+            url: url.into(),
+            alt_text: Some(alt_text.into()),
+            size: Some(ImageSize::Stretch),
+            ..Default::default()
+        }))
+    }
+
+    /// Row of columns, see [`column()`].
+    pub fn column_set(self, columns: impl IntoIterator<Item = Column>) -> Self {
+        self.element(Element::ColumnSet(ColumnSet {
+            columns: Some(columns.into_iter().collect()),
+            ..Default::default()
+        }))
+    }
+
+    /// Any other element, for layouts the shorthands above don't cover.
+    pub fn element(mut self, element: Element) -> Self {
+        self.body.push(element);
+        self
+    }
+
+    /// Button opening `url`.
+    pub fn open_url(self, title: impl Into<String>, url: impl Into<String>) -> Self {
+        self.action(Action::OpenUrl(OpenUrl {
+            url: url.into(),
+            props: ActionProps {
+                title: Some(title.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }))
+    }
+
+    /// Any other action, added after the buttons before it.
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// The finished card, for when it isn't sent as is.  `AdaptiveCardData::from` wraps it
+    /// into a message.
+    pub fn build(self) -> AdaptiveCard {
+        AdaptiveCard {
+            schema: Some(SCHEMA.to_string()),
+            version: Some("1.5".to_string()),
+            body: Some(self.body),
+            actions: Some(self.actions),
+            ..Default::default()
+        }
+    }
+}
+
+/// Column of `items`, `width` being "auto", "stretch", a pixel width or a relative weight.
+pub fn column(width: impl Into<String>, items: Vec<Element>) -> Column {
+    let width = width.into();
+    Column {
+        items: Some(items),
+        width: Some(match width.parse() {
+            Ok(weight) => Width::Weight(weight),
+            Err(_) => Width::Keyword(width),
+        }),
+        ..Default::default()
+    }
+}
+
+/// The message/attachment envelope Teams expects around a card.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdaptiveCardData {
+    #[serde(rename = "type")]
+    data_type: String,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Attachment {
+    #[serde(rename = "contentType")]
+    content_type: String,
+    #[serde(rename = "contentUrl")]
+    content_url: String,
+    content: CardContent,
+}
+
+/// Cards are either built from the typed model or taken as raw JSON, e.g. from a template.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum CardContent {
+    Built(Box<AdaptiveCard>),
+    Raw(Value),
+}
+
+impl AdaptiveCardData {
+    fn wrap(content: CardContent) -> Self {
+        AdaptiveCardData {
+            data_type: "message".to_string(),
+            attachments: vec![Attachment {
+                content_type: "application/vnd.microsoft.card.adaptive".to_string(),
+                content_url: "none".to_string(),
+                content,
+            }],
+        }
+    }
+
+    /// Wraps a card from the typed model.
+    pub fn new(card: AdaptiveCard) -> Self {
+        AdaptiveCardData::wrap(CardContent::Built(Box::new(card)))
+    }
+
+    /// Wraps a card given as raw JSON.  Fills in the card type, schema and version when the
+    /// card leaves them out.
+    pub fn raw(mut content: Value) -> Self {
+        if let Some(card) = content.as_object_mut() {
+            card.entry("type").or_insert("AdaptiveCard".into());
+            card.entry("$schema").or_insert(SCHEMA.into());
+            card.entry("version").or_insert("1.5".into());
+        }
+        AdaptiveCardData::wrap(CardContent::Raw(content))
+    }
+}
+
+impl From<Card> for AdaptiveCardData {
+    fn from(card: Card) -> Self {
+        AdaptiveCardData::new(card.build())
+    }
+}
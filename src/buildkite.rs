@@ -1,42 +1,419 @@
+use axum::http::HeaderMap;
 use serde::{Deserialize, Serialize};
 
+use crate::signature::{self, SignatureError};
+
+// Buildkite webhook payloads.  Every delivery carries an `event` field (also sent as the
+// X-Buildkite-Event header) naming one of the events below, see
+// https://buildkite.com/docs/apis/webhooks
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum BuildkiteEvent {
+    #[serde(rename = "ping")]
+    Ping(PingData),
+    #[serde(rename = "build.scheduled")]
+    BuildScheduled(BuildData),
+    #[serde(rename = "build.running")]
+    BuildRunning(BuildData),
+    #[serde(rename = "build.failing")]
+    BuildFailing(BuildData),
+    #[serde(rename = "build.finished")]
+    BuildFinished(BuildData),
+    #[serde(rename = "build.skipped")]
+    BuildSkipped(BuildData),
+    #[serde(rename = "job.scheduled")]
+    JobScheduled(JobData),
+    #[serde(rename = "job.started")]
+    JobStarted(JobData),
+    #[serde(rename = "job.finished")]
+    JobFinished(JobData),
+    #[serde(rename = "job.activated")]
+    JobActivated(JobData),
+    #[serde(rename = "agent.connected")]
+    AgentConnected(AgentData),
+    #[serde(rename = "agent.lost")]
+    AgentLost(AgentData),
+    #[serde(rename = "agent.disconnected")]
+    AgentDisconnected(AgentData),
+    #[serde(rename = "agent.stopping")]
+    AgentStopping(AgentData),
+    #[serde(rename = "agent.stopped")]
+    AgentStopped(AgentData),
+    #[serde(rename = "agent.blocked")]
+    AgentBlocked(AgentBlockedData),
+    #[serde(rename = "cluster_token.registration_blocked")]
+    ClusterTokenRegistrationBlocked(ClusterTokenData),
+}
+
+/// The names of the events above, as `name()` gives them.
+pub const EVENTS: [&str; 17] = [
+    "ping",
+    "build.scheduled",
+    "build.running",
+    "build.failing",
+    "build.finished",
+    "build.skipped",
+    "job.scheduled",
+    "job.started",
+    "job.finished",
+    "job.activated",
+    "agent.connected",
+    "agent.lost",
+    "agent.disconnected",
+    "agent.stopping",
+    "agent.stopped",
+    "agent.blocked",
+    "cluster_token.registration_blocked",
+];
+
+impl BuildkiteEvent {
+    /// Whether `name` is an event Buildkite sends, so that other payloads with an `event`
+    /// field aren't taken for Buildkite's.
+    pub fn is_known(name: &str) -> bool {
+        EVENTS.contains(&name)
+    }
+
+    /// The event name as Buildkite sends it, e.g. `build.finished`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildkiteEvent::Ping(_) => "ping",
+            BuildkiteEvent::BuildScheduled(_) => "build.scheduled",
+            BuildkiteEvent::BuildRunning(_) => "build.running",
+            BuildkiteEvent::BuildFailing(_) => "build.failing",
+            BuildkiteEvent::BuildFinished(_) => "build.finished",
+            BuildkiteEvent::BuildSkipped(_) => "build.skipped",
+            BuildkiteEvent::JobScheduled(_) => "job.scheduled",
+            BuildkiteEvent::JobStarted(_) => "job.started",
+            BuildkiteEvent::JobFinished(_) => "job.finished",
+            BuildkiteEvent::JobActivated(_) => "job.activated",
+            BuildkiteEvent::AgentConnected(_) => "agent.connected",
+            BuildkiteEvent::AgentLost(_) => "agent.lost",
+            BuildkiteEvent::AgentDisconnected(_) => "agent.disconnected",
+            BuildkiteEvent::AgentStopping(_) => "agent.stopping",
+            BuildkiteEvent::AgentStopped(_) => "agent.stopped",
+            BuildkiteEvent::AgentBlocked(_) => "agent.blocked",
+            BuildkiteEvent::ClusterTokenRegistrationBlocked(_) => {
+                "cluster_token.registration_blocked"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BuildState {
-    #[serde(rename = "failing")]
+    Creating,
+    #[serde(alias = "schedule")]
+    Scheduled,
+    Running,
+    Passed,
     Failing,
-    #[serde(rename = "schedule")]
+    Failed,
+    Blocked,
+    Canceling,
+    Canceled,
+    Skipped,
+    NotRun,
+    Finished,
+    // States Buildkite may add, by their name
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl BuildState {
+    /// How the state reads on a card, e.g. "Not run" for `not_run`.
+    pub fn label(&self) -> &str {
+        match self {
+            BuildState::Creating => "Creating",
+            BuildState::Scheduled => "Scheduled",
+            BuildState::Running => "Running",
+            BuildState::Passed => "Passed",
+            BuildState::Failing => "Failing",
+            BuildState::Failed => "Failed",
+            BuildState::Blocked => "Blocked",
+            BuildState::Canceling => "Canceling",
+            BuildState::Canceled => "Canceled",
+            BuildState::Skipped => "Skipped",
+            BuildState::NotRun => "Not run",
+            BuildState::Finished => "Finished",
+            BuildState::Other(state) => state,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Pending,
+    Waiting,
+    WaitingFailed,
+    Blocked,
+    BlockedFailed,
+    Unblocked,
+    UnblockedFailed,
+    Limiting,
+    Limited,
     Scheduled,
-    #[serde(rename = "running")]
+    Assigned,
+    Accepted,
     Running,
-    #[serde(rename = "failed")]
+    Passed,
     Failed,
-    #[serde(rename = "canceled")]
+    Canceling,
     Canceled,
+    TimingOut,
+    TimedOut,
+    Skipped,
+    Broken,
+    Expired,
+    // States Buildkite may add, by their name
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl JobState {
+    /// How the state reads on a card, e.g. "Waiting failed" for `waiting_failed`.
+    pub fn label(&self) -> &str {
+        match self {
+            JobState::Pending => "Pending",
+            JobState::Waiting => "Waiting",
+            JobState::WaitingFailed => "Waiting failed",
+            JobState::Blocked => "Blocked",
+            JobState::BlockedFailed => "Blocked failed",
+            JobState::Unblocked => "Unblocked",
+            JobState::UnblockedFailed => "Unblocked failed",
+            JobState::Limiting => "Limiting",
+            JobState::Limited => "Limited",
+            JobState::Scheduled => "Scheduled",
+            JobState::Assigned => "Assigned",
+            JobState::Accepted => "Accepted",
+            JobState::Running => "Running",
+            JobState::Passed => "Passed",
+            JobState::Failed => "Failed",
+            JobState::Canceling => "Canceling",
+            JobState::Canceled => "Canceled",
+            JobState::TimingOut => "Timing out",
+            JobState::TimedOut => "Timed out",
+            JobState::Skipped => "Skipped",
+            JobState::Broken => "Broken",
+            JobState::Expired => "Expired",
+            JobState::Other(state) => state,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentConnectionState {
+    Connected,
+    Disconnected,
+    Stopping,
+    Stopped,
+    Lost,
+    NeverConnected,
+    // States Buildkite may add, by their name
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl AgentConnectionState {
+    /// How the state reads on a card, e.g. "Never connected" for `never_connected`.
+    pub fn label(&self) -> &str {
+        match self {
+            AgentConnectionState::Connected => "Connected",
+            AgentConnectionState::Disconnected => "Disconnected",
+            AgentConnectionState::Stopping => "Stopping",
+            AgentConnectionState::Stopped => "Stopped",
+            AgentConnectionState::Lost => "Lost",
+            AgentConnectionState::NeverConnected => "Never connected",
+            AgentConnectionState::Other(state) => state,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sender {
+    pub id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Creator {
+    pub id: Option<String>,
+    pub name: String,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Pipeline {
     pub name: String,
+    pub slug: Option<String>,
     pub web_url: String,
     pub repository: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Build {
-    pub number: String,
-    pub commit: String,
-    pub created_at: String,
+    pub number: u64,
+    pub web_url: Option<String>,
     pub state: BuildState,
+    pub message: Option<String>,
+    pub commit: String,
+    pub branch: Option<String>,
+    pub creator: Option<Creator>,
+    pub created_at: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    pub name: Option<String>,
+    pub step_key: Option<String>,
+    pub state: JobState,
+    pub web_url: Option<String>,
+    pub exit_status: Option<i32>,
+    #[serde(default)]
+    pub soft_failed: bool,
+    pub agent: Option<Agent>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Agent {
+    pub id: String,
+    pub name: String,
+    pub web_url: Option<String>,
+    pub connection_state: AgentConnectionState,
+    pub hostname: Option<String>,
+    pub ip_address: Option<String>,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub meta_data: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Organization {
+    pub name: String,
+    pub slug: Option<String>,
+    pub web_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Service {
+    pub id: String,
+    pub provider: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterToken {
+    pub uuid: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub allowed_ip_addresses: Option<String>,
+}
+
+// build.* events
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BuildData {
+    pub build: Build,
     pub pipeline: Pipeline,
+    pub sender: Sender,
+}
+
+// job.* events
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobData {
+    pub job: Job,
     pub build: Build,
-    pub sender_name: String,
-    pub creator_avatar: String,
+    pub pipeline: Pipeline,
+    pub sender: Sender,
+}
+
+// agent.* events, apart from agent.blocked
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentData {
+    pub agent: Agent,
+    pub sender: Sender,
 }
 
-// impl BuildData {
-//     pub fn pipeline(self) -> Pipeline { self.pipeline }
-// }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentBlockedData {
+    pub blocked_ip: String,
+    pub cluster_token: Option<ClusterToken>,
+    pub agent: Agent,
+    pub sender: Sender,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterTokenData {
+    pub blocked_ip: String,
+    pub cluster_token: ClusterToken,
+    pub sender: Sender,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingData {
+    pub service: Service,
+    pub organization: Organization,
+    pub sender: Sender,
+}
+
+// Checks whichever of Buildkite's two webhook authentication schemes is configured.  With a
+// signing secret, X-Buildkite-Signature is `timestamp=<unix>,signature=<hex>` where the
+// signature is HMAC-SHA256 over "<timestamp>.<body>".  With a token, X-Buildkite-Token must
+// match it exactly.  A channel with neither configured accepts everything, as before.
+pub fn verify_request(
+    headers: &HeaderMap,
+    body: &[u8],
+    token: Option<&str>,
+    signing_secret: Option<&str>,
+    now: u64,
+) -> Result<(), SignatureError> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if let (Some(secret), Some(signature_header)) =
+        (signing_secret, header("X-Buildkite-Signature"))
+    {
+        return verify_signature(signature_header, body, secret, now);
+    }
+    if let (Some(expected), Some(given)) = (token, header("X-Buildkite-Token")) {
+        if signature::constant_time_eq(expected.as_bytes(), given.as_bytes()) {
+            return Ok(());
+        }
+        return Err(SignatureError::Mismatch);
+    }
+    match (signing_secret, token) {
+        (Some(_), _) => Err(SignatureError::MissingHeader("X-Buildkite-Signature")),
+        (None, Some(_)) => Err(SignatureError::MissingHeader("X-Buildkite-Token")),
+        (None, None) => Ok(()),
+    }
+}
+
+fn verify_signature(
+    signature_header: &str,
+    body: &[u8],
+    secret: &str,
+    now: u64,
+) -> Result<(), SignatureError> {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in signature_header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("timestamp", value)) => timestamp = value.parse::<u64>().ok(),
+            Some(("signature", value)) => signature = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return Err(SignatureError::Malformed("X-Buildkite-Signature"));
+    };
+    signature::check_timestamp(timestamp, now)?;
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    signature::verify_hmac_sha256_hex(secret.as_bytes(), &message, signature)
+}
@@ -0,0 +1,1018 @@
+//! Typed Adaptive Card 1.5 model, see <https://adaptivecards.io/explorer/>
+//!
+//! Every object keeps the properties it doesn't model (host extensions such as `msteams`,
+//! properties from later schema versions) in `extra`, and elements or actions of a type this
+//! module doesn't know come through as `Unknown`, so any card survives a serde round trip.
+//! Enumerated values are matched case-insensitively, as clients do, and written back in the
+//! schema's PascalCase spelling.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+
+// Enumerated string property.  Deserializing ignores case; serializing uses the given spelling.
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl $name {
+            pub const VALUES: &'static [&'static str] = &[$($value),+];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $value),+
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                $(if value.eq_ignore_ascii_case($value) {
+                    return Ok($name::$variant);
+                })+
+                Err(format!(
+                    "unknown {} {value:?}, expected one of {}",
+                    stringify!($name),
+                    Self::VALUES.join(", ")
+                ))
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                value
+                    .parse()
+                    .map_err(|_| de::Error::unknown_variant(&value, Self::VALUES))
+            }
+        }
+    };
+}
+
+string_enum!(Color {
+    Default = "Default",
+    Dark = "Dark",
+    Light = "Light",
+    Accent = "Accent",
+    Good = "Good",
+    Warning = "Warning",
+    Attention = "Attention",
+});
+
+string_enum!(FontType {
+    Default = "Default",
+    Monospace = "Monospace",
+});
+
+string_enum!(FontSize {
+    Default = "Default",
+    Small = "Small",
+    Medium = "Medium",
+    Large = "Large",
+    ExtraLarge = "ExtraLarge",
+});
+
+string_enum!(FontWeight {
+    Default = "Default",
+    Lighter = "Lighter",
+    Bolder = "Bolder",
+});
+
+string_enum!(TextBlockStyle {
+    Default = "Default",
+    Heading = "Heading",
+});
+
+string_enum!(HorizontalAlignment {
+    Left = "Left",
+    Center = "Center",
+    Right = "Right",
+});
+
+string_enum!(VerticalAlignment {
+    Top = "Top",
+    Center = "Center",
+    Bottom = "Bottom",
+});
+
+string_enum!(Spacing {
+    Default = "Default",
+    None = "None",
+    Small = "Small",
+    Medium = "Medium",
+    Large = "Large",
+    ExtraLarge = "ExtraLarge",
+    Padding = "Padding",
+});
+
+string_enum!(BlockElementHeight {
+    Auto = "Auto",
+    Stretch = "Stretch",
+});
+
+string_enum!(ImageSize {
+    Auto = "Auto",
+    Stretch = "Stretch",
+    Small = "Small",
+    Medium = "Medium",
+    Large = "Large",
+});
+
+string_enum!(ImageStyle {
+    Default = "Default",
+    Person = "Person",
+});
+
+string_enum!(ImageFillMode {
+    Cover = "Cover",
+    RepeatHorizontally = "RepeatHorizontally",
+    RepeatVertically = "RepeatVertically",
+    Repeat = "Repeat",
+});
+
+string_enum!(ContainerStyle {
+    Default = "Default",
+    Emphasis = "Emphasis",
+    Good = "Good",
+    Attention = "Attention",
+    Warning = "Warning",
+    Accent = "Accent",
+});
+
+string_enum!(TextInputStyle {
+    Text = "Text",
+    Tel = "Tel",
+    Url = "Url",
+    Email = "Email",
+    Password = "Password",
+});
+
+string_enum!(ChoiceInputStyle {
+    Compact = "Compact",
+    Expanded = "Expanded",
+    Filtered = "Filtered",
+});
+
+string_enum!(ActionStyle {
+    Default = "Default",
+    Positive = "Positive",
+    Destructive = "Destructive",
+});
+
+string_enum!(ActionMode {
+    Primary = "Primary",
+    Secondary = "Secondary",
+});
+
+string_enum!(AssociatedInputs {
+    Auto = "Auto",
+    None = "None",
+});
+
+string_enum!(FallbackOption { Drop = "drop" });
+
+/// Fixed `type` of objects that only appear in one place, so the struct can write it itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CardType {
+    #[default]
+    AdaptiveCard,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnType {
+    #[default]
+    Column,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableRowType {
+    #[default]
+    TableRow,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableCellType {
+    #[default]
+    TableCell,
+}
+
+/// What a client that doesn't support an element or action shows instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Fallback<T> {
+    Option(FallbackOption),
+    Item(Box<T>),
+}
+
+/// "auto" or "stretch", or a pixel height like "50px" where the element allows one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Height {
+    Block(BlockElementHeight),
+    Pixels(String),
+}
+
+/// Relative weight, or "auto", "stretch" or a pixel width like "50px".
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Width {
+    Weight(Number),
+    Keyword(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdaptiveCard {
+    #[serde(rename = "type", default)]
+    pub card_type: CardType,
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Vec<Element>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_image: Option<BackgroundImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtl: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speak: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_content_alignment: Option<VerticalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<Refresh>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<Authentication>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BackgroundImage {
+    Url(String),
+    Image(BackgroundImageProps),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackgroundImageProps {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_mode: Option<ImageFillMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_alignment: Option<VerticalAlignment>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Refresh {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_ids: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authentication {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_exchange_resource: Option<TokenExchangeResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buttons: Option<Vec<AuthCardButton>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenExchangeResource {
+    pub id: String,
+    pub uri: String,
+    pub provider_id: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthCardButton {
+    #[serde(rename = "type")]
+    pub button_type: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Element {
+    TextBlock(TextBlock),
+    Image(Image),
+    Media(Media),
+    RichTextBlock(RichTextBlock),
+    Container(Container),
+    ColumnSet(ColumnSet),
+    FactSet(FactSet),
+    ImageSet(ImageSet),
+    ActionSet(ActionSet),
+    Table(Table),
+    #[serde(rename = "Input.Text")]
+    InputText(InputText),
+    #[serde(rename = "Input.Number")]
+    InputNumber(InputNumber),
+    #[serde(rename = "Input.Date")]
+    InputDate(InputDate),
+    #[serde(rename = "Input.Time")]
+    InputTime(InputTime),
+    #[serde(rename = "Input.Toggle")]
+    InputToggle(InputToggle),
+    #[serde(rename = "Input.ChoiceSet")]
+    InputChoiceSet(InputChoiceSet),
+    /// Anything else, kept as is: element types from other schema versions or hosts, and
+    /// known types whose properties don't fit the model
+    #[serde(untagged)]
+    Unknown(Map<String, Value>),
+}
+
+/// Properties every element has.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ElementProps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spacing: Option<Spacing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<Height>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback<Element>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextBlock {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_type: Option<FontType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_subtle: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_lines: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<FontSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<FontWeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<TextBlockStyle>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<ImageSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ImageStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<String>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Media {
+    pub sources: Vec<MediaSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_text: Option<String>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaSource {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RichTextBlock {
+    pub inlines: Vec<Inline>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_alignment: Option<HorizontalAlignment>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Rich text is a list of TextRuns, or bare strings for unformatted runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Inline {
+    TextRun(TextRun),
+    #[serde(untagged)]
+    Text(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextRun {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_type: Option<FontType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_subtle: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<FontSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<FontWeight>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Container {
+    pub items: Vec<Element>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_content_alignment: Option<VerticalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bleed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_image: Option<BackgroundImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtl: Option<bool>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<Column>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bleed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_alignment: Option<HorizontalAlignment>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Column {
+    #[serde(rename = "type", default)]
+    pub column_type: ColumnType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<Element>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<Width>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_content_alignment: Option<VerticalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bleed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_image: Option<BackgroundImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtl: Option<bool>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactSet {
+    pub facts: Vec<Fact>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fact {
+    pub title: String,
+    pub value: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSet {
+    #[serde(with = "image_list")]
+    pub images: Vec<Image>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<ImageSize>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// ImageSet images are Image elements, "type" and all, but can't be anything else.
+mod image_list {
+    use super::Image;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    #[serde(tag = "type")]
+    enum TaggedRef<'a> {
+        Image(&'a Image),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(tag = "type")]
+    enum Tagged {
+        Image(Image),
+    }
+
+    pub fn serialize<S: Serializer>(images: &[Image], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(images.iter().map(TaggedRef::Image))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Image>, D::Error> {
+        let images = Vec::<Tagged>::deserialize(deserializer)?;
+        Ok(images
+            .into_iter()
+            .map(|Tagged::Image(image)| image)
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionSet {
+    pub actions: Vec<Action>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Table {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<TableColumnDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<TableRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_row_as_header: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_grid_lines: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid_style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_cell_content_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_cell_content_alignment: Option<VerticalAlignment>,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableColumnDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<Width>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_cell_content_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_cell_content_alignment: Option<VerticalAlignment>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableRow {
+    #[serde(rename = "type", default)]
+    pub row_type: TableRowType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cells: Option<Vec<TableCell>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizontal_cell_content_alignment: Option<HorizontalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_cell_content_alignment: Option<VerticalAlignment>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableCell {
+    #[serde(rename = "type", default)]
+    pub cell_type: TableCellType,
+    pub items: Vec<Element>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub select_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ContainerStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_content_alignment: Option<VerticalAlignment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bleed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_image: Option<BackgroundImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtl: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Properties every input has, on top of the element ones.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputProps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputText {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_multiline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<TextInputStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_action: Option<Box<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputNumber {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Dates are YYYY-MM-DD.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Times are HH:MM.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputTime {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputToggle {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_off: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_on: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputChoiceSet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<Choice>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_multi_select: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ChoiceInputStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    #[serde(flatten)]
+    pub input: InputProps,
+    #[serde(flatten)]
+    pub props: ElementProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+    pub title: String,
+    pub value: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Action {
+    #[serde(rename = "Action.OpenUrl")]
+    OpenUrl(OpenUrl),
+    #[serde(rename = "Action.Submit")]
+    Submit(Submit),
+    #[serde(rename = "Action.ShowCard")]
+    ShowCard(ShowCard),
+    #[serde(rename = "Action.ToggleVisibility")]
+    ToggleVisibility(ToggleVisibility),
+    #[serde(rename = "Action.Execute")]
+    Execute(Execute),
+    /// Action types from other schema versions or hosts, kept as is
+    #[serde(untagged)]
+    Unknown(Map<String, Value>),
+}
+
+/// Properties every action has.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionProps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ActionStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback<Action>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ActionMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenUrl {
+    pub url: String,
+    #[serde(flatten)]
+    pub props: ActionProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub associated_inputs: Option<AssociatedInputs>,
+    #[serde(flatten)]
+    pub props: ActionProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShowCard {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card: Option<Box<AdaptiveCard>>,
+    #[serde(flatten)]
+    pub props: ActionProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToggleVisibility {
+    pub target_elements: Vec<TargetElement>,
+    #[serde(flatten)]
+    pub props: ActionProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An element id, which toggles, or an id with the visibility to set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TargetElement {
+    Id(String),
+    Target {
+        #[serde(rename = "elementId")]
+        element_id: String,
+        #[serde(rename = "isVisible", skip_serializing_if = "Option::is_none")]
+        is_visible: Option<bool>,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Execute {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verb: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub associated_inputs: Option<AssociatedInputs>,
+    #[serde(flatten)]
+    pub props: ActionProps,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -0,0 +1,380 @@
+use lessons::builder::Card;
+use lessons::card::Color;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+
+// Amazon EventBridge event, as delivered by an API destination.  The envelope is the same for
+// every service and `detail-type` says what is in `detail`, see
+// https://docs.aws.amazon.com/eventbridge/latest/ref/overiew-event-structure.html
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventBridgeEvent {
+    pub id: Option<String>,
+    pub detail_type: String,
+    pub source: String,
+    pub account: Option<String>,
+    pub time: Option<String>,
+    pub region: Option<String>,
+    #[serde(default)]
+    pub resources: Vec<String>,
+    pub detail: serde_json::Value,
+}
+
+// Details we have cards for.  Anything else, including a known detail-type whose detail does
+// not parse, is shown as a generic FactSet so new rules work without a code change.
+#[derive(Debug)]
+pub enum Detail {
+    CodePipeline(CodePipelineDetail),
+    EcsTask(EcsTaskDetail),
+    GuardDuty(GuardDutyFinding),
+    Health(HealthDetail),
+    Other(serde_json::Value),
+}
+
+impl EventBridgeEvent {
+    pub fn parsed_detail(&self) -> Detail {
+        let detail = self.detail.clone();
+        let parsed = match self.detail_type.as_str() {
+            "CodePipeline Pipeline Execution State Change"
+            | "CodePipeline Stage Execution State Change"
+            | "CodePipeline Action Execution State Change" => {
+                serde_json::from_value(detail).map(Detail::CodePipeline)
+            }
+            "ECS Task State Change" => serde_json::from_value(detail).map(Detail::EcsTask),
+            "GuardDuty Finding" => serde_json::from_value(detail).map(Detail::GuardDuty),
+            "AWS Health Event" => serde_json::from_value(detail).map(Detail::Health),
+            _ => return Detail::Other(detail),
+        };
+        parsed.unwrap_or_else(|err| {
+            println!("Showing {} as a generic event: {err}", self.detail_type);
+            Detail::Other(self.detail.clone())
+        })
+    }
+
+    fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or("us-east-1")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CodePipelineDetail {
+    pub pipeline: String,
+    pub execution_id: String,
+    pub state: String,
+    pub stage: Option<String>,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EcsContainer {
+    pub name: String,
+    pub exit_code: Option<i64>,
+    pub reason: Option<String>,
+    pub last_status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EcsTaskDetail {
+    pub cluster_arn: String,
+    pub task_arn: String,
+    pub task_definition_arn: Option<String>,
+    pub group: Option<String>,
+    pub last_status: String,
+    pub desired_status: Option<String>,
+    pub stop_code: Option<String>,
+    pub stopped_reason: Option<String>,
+    #[serde(default)]
+    pub containers: Vec<EcsContainer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardDutyResource {
+    pub resource_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuardDutyService {
+    pub count: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardDutyFinding {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub finding_type: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub severity: f64,
+    pub resource: Option<GuardDutyResource>,
+    pub service: Option<GuardDutyService>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthDescription {
+    pub latest_description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthEntity {
+    pub entity_value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthDetail {
+    pub event_arn: String,
+    pub service: String,
+    pub event_type_code: String,
+    pub event_type_category: Option<String>,
+    pub status_code: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub event_description: Vec<HealthDescription>,
+    #[serde(default)]
+    pub affected_entities: Vec<HealthEntity>,
+}
+
+// Resource name from an ARN, e.g. "default" from arn:aws:ecs:...:cluster/default or
+// "web:12" from a task definition ARN.
+fn arn_name(arn: &str) -> &str {
+    arn.rsplit('/').next().unwrap_or(arn)
+}
+
+// Envelope facts shown on every card.
+fn envelope_facts(event: &EventBridgeEvent) -> Vec<(String, String)> {
+    let mut facts = vec![];
+    if let Some(account) = &event.account {
+        facts.push(("Account".to_string(), account.clone()));
+    }
+    if let Some(region) = &event.region {
+        facts.push(("Region".to_string(), region.clone()));
+    }
+    if let Some(time) = &event.time {
+        facts.push(("Time".to_string(), time.clone()));
+    }
+    facts
+}
+
+// A page of the AWS console in `region`, with `segments` escaped into its path and the region
+// in the query as the console expects.  None when the region can't be part of a host name.
+fn console_url(region: &str, segments: &[&str]) -> Option<Url> {
+    let mut url = Url::parse(&format!("https://{region}.console.aws.amazon.com")).ok()?;
+    url.path_segments_mut().ok()?.extend(segments);
+    url.query_pairs_mut().append_pair("region", region);
+    Some(url)
+}
+
+fn codepipeline_card(event: &EventBridgeEvent, detail: CodePipelineDetail) -> AdaptiveCardData {
+    let color = match detail.state.as_str() {
+        "SUCCEEDED" => Color::Good,
+        "FAILED" => Color::Attention,
+        "CANCELED" | "STOPPED" | "STOPPING" | "SUPERSEDED" | "ABANDONED" => Color::Warning,
+        _ => Color::Accent,
+    };
+    let step: Vec<&str> = [
+        Some(detail.pipeline.as_str()),
+        detail.stage.as_deref(),
+        detail.action.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let mut facts = vec![("Execution".to_string(), detail.execution_id.clone())];
+    facts.extend(envelope_facts(event));
+    let mut card = Card::new()
+        .title(format!("{} {}", step.join(" / "), detail.state), color)
+        .facts(facts);
+    let execution = [
+        "codesuite",
+        "codepipeline",
+        "pipelines",
+        &detail.pipeline,
+        "executions",
+        &detail.execution_id,
+        "timeline",
+    ];
+    if let Some(url) = console_url(event.region(), &execution) {
+        card = card.open_url("View execution", url.to_string());
+    }
+    card.into()
+}
+
+fn ecs_task_card(event: &EventBridgeEvent, detail: EcsTaskDetail) -> AdaptiveCardData {
+    let failed = detail
+        .containers
+        .iter()
+        .any(|c| c.exit_code.is_some_and(|code| code != 0));
+    let color = match detail.last_status.as_str() {
+        "STOPPED" if failed => Color::Attention,
+        "STOPPED" => Color::Warning,
+        "RUNNING" => Color::Good,
+        _ => Color::Accent,
+    };
+    let cluster = arn_name(&detail.cluster_arn);
+    let task = arn_name(&detail.task_arn);
+    let name = detail.group.as_deref().unwrap_or(task);
+    let mut card = Card::new().title(
+        format!("ECS task {} {}", name, detail.last_status.to_lowercase()),
+        color,
+    );
+    if let Some(reason) = &detail.stopped_reason {
+        card = card.text(reason.clone());
+    }
+    let mut facts = vec![("Cluster".to_string(), cluster.to_string())];
+    if let Some(task_definition) = &detail.task_definition_arn {
+        facts.push((
+            "Task definition".to_string(),
+            arn_name(task_definition).to_string(),
+        ));
+    }
+    if let Some(stop_code) = &detail.stop_code {
+        facts.push(("Stop code".to_string(), stop_code.clone()));
+    }
+    for container in &detail.containers {
+        let mut value = match container.exit_code {
+            Some(code) => format!("exit code {code}"),
+            None => container.last_status.clone().unwrap_or_default(),
+        };
+        if let Some(reason) = &container.reason {
+            value.push_str(&format!(" ({reason})"));
+        }
+        facts.push((format!("Container {}", container.name), value));
+    }
+    facts.extend(envelope_facts(event));
+    card = card.facts(facts);
+    let task = ["ecs", "v2", "clusters", cluster, "tasks", task];
+    if let Some(url) = console_url(event.region(), &task) {
+        card = card.open_url("View task", url.to_string());
+    }
+    card.into()
+}
+
+fn guardduty_card(event: &EventBridgeEvent, finding: GuardDutyFinding) -> AdaptiveCardData {
+    // GuardDuty's own severity bands
+    let (severity, color) = match finding.severity {
+        s if s >= 7.0 => ("High", Color::Attention),
+        s if s >= 4.0 => ("Medium", Color::Warning),
+        _ => ("Low", Color::Accent),
+    };
+    let mut card = Card::new().title(
+        format!("[{severity} {:.1}] {}", finding.severity, finding.title),
+        color,
+    );
+    if let Some(description) = &finding.description {
+        card = card.text(description.clone());
+    }
+    let mut facts = vec![("Type".to_string(), finding.finding_type.clone())];
+    if let Some(resource_type) = finding.resource.and_then(|r| r.resource_type) {
+        facts.push(("Resource".to_string(), resource_type));
+    }
+    if let Some(count) = finding.service.and_then(|s| s.count) {
+        facts.push(("Count".to_string(), count.to_string()));
+    }
+    facts.extend(envelope_facts(event));
+    card = card.facts(facts);
+    // The GuardDuty console routes on the fragment
+    if let Some(mut url) = console_url(event.region(), &["guardduty", "home"]) {
+        url.set_fragment(Some(&format!(
+            "/findings?macros=current&fId={}",
+            finding.id
+        )));
+        card = card.open_url("View finding", url.to_string());
+    }
+    card.into()
+}
+
+fn health_card(event: &EventBridgeEvent, detail: HealthDetail) -> AdaptiveCardData {
+    let color = match detail.status_code.as_deref() {
+        Some("closed") => Color::Good,
+        Some("upcoming") => Color::Warning,
+        _ if detail.event_type_category.as_deref() == Some("issue") => Color::Attention,
+        _ => Color::Accent,
+    };
+    let mut card = Card::new().title(
+        format!("AWS Health: {} {}", detail.service, detail.event_type_code),
+        color,
+    );
+    if let Some(description) = detail.event_description.first() {
+        card = card.text(description.latest_description.clone());
+    }
+    let mut facts = vec![];
+    if let Some(category) = &detail.event_type_category {
+        facts.push(("Category".to_string(), category.clone()));
+    }
+    if let Some(status) = &detail.status_code {
+        facts.push(("Status".to_string(), status.clone()));
+    }
+    if let Some(start) = &detail.start_time {
+        facts.push(("Start".to_string(), start.clone()));
+    }
+    if let Some(end) = &detail.end_time {
+        facts.push(("End".to_string(), end.clone()));
+    }
+    if !detail.affected_entities.is_empty() {
+        let entities: Vec<&str> = detail
+            .affected_entities
+            .iter()
+            .map(|e| e.entity_value.as_str())
+            .collect();
+        facts.push(("Affected".to_string(), entities.join(", ")));
+    }
+    facts.extend(envelope_facts(event));
+    card.facts(facts)
+        .open_url(
+            "Open AWS Health",
+            format!(
+                "https://health.aws.amazon.com/health/home#/account/event-log?eventID={}",
+                detail.event_arn
+            ),
+        )
+        .into()
+}
+
+fn format_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn generic_card(event: &EventBridgeEvent, detail: serde_json::Value) -> AdaptiveCardData {
+    let card = Card::new()
+        .title(event.detail_type.clone(), Color::Default)
+        .detail(format!("Source: {}", event.source));
+    let mut facts: Vec<(String, String)> = match &detail {
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| (key.clone(), format_value(value)))
+            .collect(),
+        serde_json::Value::Null => vec![],
+        value => vec![("detail".to_string(), format_value(value))],
+    };
+    facts.extend(envelope_facts(event));
+    if !event.resources.is_empty() {
+        facts.push(("Resources".to_string(), event.resources.join(", ")));
+    }
+    card.facts(facts).into()
+}
+
+impl From<EventBridgeEvent> for AdaptiveCardData {
+    fn from(event: EventBridgeEvent) -> Self {
+        match event.parsed_detail() {
+            Detail::CodePipeline(detail) => codepipeline_card(&event, detail),
+            Detail::EcsTask(detail) => ecs_task_card(&event, detail),
+            Detail::GuardDuty(finding) => guardduty_card(&event, finding),
+            Detail::Health(detail) => health_card(&event, detail),
+            Detail::Other(detail) => generic_card(&event, detail),
+        }
+    }
+}
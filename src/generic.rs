@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::template;
+
+// Config-driven card for any JSON payload, so a new vendor only needs an entry in
+// mappings.json.  Every string in a mapping is an expression: a JSONPath such as
+// `$.incident.name`, text with `${...}` bindings in the Adaptive Card Templating language the
+// built-in cards use (as in `${incident.name}`, or `${jsonPath('$.incident.components[*]')}`
+// for JSONPath), or literal text.  A mapping either describes the card field by field, or
+// gives a whole Adaptive Card as `template`, expanded like the built-in ones:
+//
+//   "statuspage": {
+//     "title": "${incident.name} is ${incident.status}",
+//     "color": {"value": "$.incident.status", "map": {"resolved": "Good"}, "default": "Warning"},
+//     "text": "$.incident.incident_updates[0].body",
+//     "facts": [{"title": "Impact", "value": "$.incident.impact"}],
+//     "actions": [{"title": "View incident", "url": "$.incident.shortlink"}]
+//   }
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Mapping {
+    pub title: Option<String>,
+    pub color: Option<ColorMapping>,
+    pub text: Option<String>,
+    #[serde(default)]
+    pub facts: Vec<FactMapping>,
+    pub image: Option<String>,
+    #[serde(default)]
+    pub actions: Vec<ActionMapping>,
+    pub template: Option<Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ColorMapping {
+    // Expression that evaluates to an Adaptive Card color name
+    Value(String),
+    // Expression whose result is looked up in `map`
+    Lookup {
+        value: String,
+        map: HashMap<String, String>,
+        default: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FactMapping {
+    pub title: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActionMapping {
+    pub title: String,
+    pub url: String,
+}
+
+// A bare JSONPath, as opposed to text that may hold `${...}` bindings.
+fn is_path(expression: &str) -> bool {
+    expression.starts_with('$') && !expression.contains("${")
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        Value::Array(items) if items.iter().all(|i| !i.is_object() && !i.is_array()) => {
+            items.iter().map(to_text).collect::<Vec<_>>().join(", ")
+        }
+        value => value.to_string(),
+    }
+}
+
+// Checks the `${...}` bindings in a string parse, for checking mappings when they are loaded.
+// Bindings holding JSONPath, as mappings could have before they were expanded like the
+// built-in templates, would only come out empty, so they are refused.
+fn check_bindings(text: &str) -> Result<(), String> {
+    if text.contains("${$.") {
+        return Err(format!(
+            "{text}: use jsonPath('$...') for JSONPath in a ${{...}} binding"
+        ));
+    }
+    template::check(&Value::String(text.to_string())).map_err(|err| err.to_string())
+}
+
+fn check(expression: &str) -> Result<(), String> {
+    if is_path(expression) {
+        template::json_path(&Value::Null, expression)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    } else {
+        check_bindings(expression)
+    }
+}
+
+// Evaluates an expression to text; None when a bare JSONPath matches nothing, or when the
+// expression fails on this payload.
+pub fn render(expression: &str, data: &Value) -> Option<String> {
+    let value = if is_path(expression) {
+        template::json_path(data, expression).map(|value| (!value.is_null()).then_some(value))
+    } else {
+        template::expand(&Value::String(expression.to_string()), data).map(Some)
+    };
+    match value {
+        Ok(value) => value.map(|value| to_text(&value)),
+        Err(err) => {
+            println!("Generic mapping expression {expression}: {err}");
+            None
+        }
+    }
+}
+
+fn template_strings<'a>(template: &'a Value, strings: &mut Vec<&'a str>) {
+    match template {
+        Value::String(text) => strings.push(text),
+        Value::Array(items) => items.iter().for_each(|v| template_strings(v, strings)),
+        Value::Object(fields) => fields.values().for_each(|v| template_strings(v, strings)),
+        _ => {}
+    }
+}
+
+impl Mapping {
+    // Checks every expression in the mapping parses, so mistakes show up at startup rather
+    // than as silently empty cards.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut expressions: Vec<&str> = vec![];
+        expressions.extend(self.title.as_deref());
+        expressions.extend(self.text.as_deref());
+        expressions.extend(self.image.as_deref());
+        match &self.color {
+            Some(ColorMapping::Value(value)) | Some(ColorMapping::Lookup { value, .. }) => {
+                expressions.push(value)
+            }
+            None => {}
+        }
+        expressions.extend(self.facts.iter().map(|f| f.value.as_str()));
+        expressions.extend(self.actions.iter().map(|a| a.url.as_str()));
+        expressions.into_iter().try_for_each(check)?;
+        let mut strings = vec![];
+        if let Some(template) = &self.template {
+            template_strings(template, &mut strings);
+        }
+        strings.into_iter().try_for_each(check_bindings)
+    }
+
+    // Unknown color names fall back to the default color.
+    fn color(&self, data: &Value) -> Color {
+        match &self.color {
+            Some(ColorMapping::Value(value)) => render(value, data),
+            Some(ColorMapping::Lookup {
+                value,
+                map,
+                default,
+            }) => render(value, data)
+                .and_then(|key| map.get(&key).cloned())
+                .or(default.clone()),
+            None => None,
+        }
+        .and_then(|color| color.parse().ok())
+        .unwrap_or(Color::Default)
+    }
+
+    // The Teams message for `data`: the expanded template if there is one, otherwise a card
+    // assembled from the individual fields.
+    pub fn apply(&self, data: &Value) -> Value {
+        if let Some(template) = &self.template {
+            let card = match template::expand(template, data) {
+                Ok(card) => AdaptiveCardData::raw(card),
+                Err(err) => Card::new()
+                    .title("Could not render the card", Color::Attention)
+                    .text(err.to_string())
+                    .into(),
+            };
+            return serde_json::to_value(card).unwrap();
+        }
+        let title = self
+            .title
+            .as_deref()
+            .and_then(|title| render(title, data))
+            .unwrap_or("Notification".to_string());
+        let mut card = Card::new().title(title, self.color(data));
+        if let Some(text) = self.text.as_deref().and_then(|text| render(text, data)) {
+            card = card.text(text);
+        }
+        let facts: Vec<(&str, String)> = self
+            .facts
+            .iter()
+            .filter_map(|fact| Some((fact.title.as_str(), render(&fact.value, data)?)))
+            .collect();
+        card = card.facts(facts);
+        if let Some(url) = self.image.as_deref().and_then(|image| render(image, data)) {
+            card = card.image(url, "Image");
+        }
+        for action in &self.actions {
+            if let Some(url) = render(&action.url, data).filter(|url| !url.is_empty()) {
+                card = card.open_url(&action.title, url);
+            }
+        }
+        serde_json::to_value(AdaptiveCardData::from(card)).unwrap()
+    }
+}
@@ -0,0 +1,512 @@
+use axum::http::HeaderMap;
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{de::Error, Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::signature::{self, SignatureError};
+
+// GitHub webhook payloads.  GitHub names the event only in the X-GitHub-Event header, so the
+// payload is picked by `GithubEvent::parse` rather than by a tag in the body, see
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads
+#[derive(Debug, Serialize, Deserialize)]
+pub enum GithubEvent {
+    Ping(PingData),
+    WorkflowRun(WorkflowRunData),
+    WorkflowJob(WorkflowJobData),
+    CheckSuite(CheckSuiteData),
+    PullRequest(PullRequestData),
+    Release(ReleaseData),
+    DeploymentStatus(DeploymentStatusData),
+}
+
+impl GithubEvent {
+    pub fn parse(event: &str, body: &[u8]) -> Result<GithubEvent, serde_json::Error> {
+        Ok(match event {
+            "ping" => GithubEvent::Ping(serde_json::from_slice(body)?),
+            "workflow_run" => GithubEvent::WorkflowRun(serde_json::from_slice(body)?),
+            "workflow_job" => GithubEvent::WorkflowJob(serde_json::from_slice(body)?),
+            "check_suite" => GithubEvent::CheckSuite(serde_json::from_slice(body)?),
+            "pull_request" => GithubEvent::PullRequest(serde_json::from_slice(body)?),
+            "release" => GithubEvent::Release(serde_json::from_slice(body)?),
+            "deployment_status" => GithubEvent::DeploymentStatus(serde_json::from_slice(body)?),
+            _ => {
+                return Err(serde_json::Error::custom(format!(
+                    "unsupported GitHub event {event}"
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Requested,
+    Queued,
+    Pending,
+    Waiting,
+    InProgress,
+    Completed,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    Pending,
+    Queued,
+    InProgress,
+    Success,
+    Failure,
+    Error,
+    Inactive,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct User {
+    pub login: String,
+    pub avatar_url: Option<String>,
+    pub html_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Repository {
+    pub full_name: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct App {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub name: Option<String>,
+    pub run_number: u64,
+    pub event: String,
+    pub status: Status,
+    pub conclusion: Option<Conclusion>,
+    pub html_url: String,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub actor: Option<User>,
+    pub run_started_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowJob {
+    pub name: String,
+    pub workflow_name: Option<String>,
+    pub status: Status,
+    pub conclusion: Option<Conclusion>,
+    pub html_url: String,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub runner_name: Option<String>,
+    pub started_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckSuite {
+    pub status: Option<Status>,
+    pub conclusion: Option<Conclusion>,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub app: Option<App>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Branch {
+    #[serde(rename = "ref")]
+    pub branch_ref: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub html_url: String,
+    pub state: String,
+    #[serde(default)]
+    pub merged: bool,
+    #[serde(default)]
+    pub draft: bool,
+    pub user: User,
+    pub head: Branch,
+    pub base: Branch,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    pub author: User,
+    pub body: Option<String>,
+    pub published_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Deployment {
+    pub sha: String,
+    #[serde(rename = "ref")]
+    pub deployment_ref: String,
+    pub environment: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeploymentStatus {
+    pub state: DeploymentState,
+    pub description: Option<String>,
+    pub environment: Option<String>,
+    pub target_url: Option<String>,
+    pub log_url: Option<String>,
+    pub environment_url: Option<String>,
+    pub creator: Option<User>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingData {
+    pub zen: String,
+    pub hook_id: u64,
+    pub repository: Option<Repository>,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowRunData {
+    pub action: String,
+    pub workflow_run: WorkflowRun,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowJobData {
+    pub action: String,
+    pub workflow_job: WorkflowJob,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckSuiteData {
+    pub action: String,
+    pub check_suite: CheckSuite,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequestData {
+    pub action: String,
+    pub number: u64,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReleaseData {
+    pub action: String,
+    pub release: Release,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeploymentStatusData {
+    pub action: String,
+    pub deployment_status: DeploymentStatus,
+    pub deployment: Deployment,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+// X-Hub-Signature-256 is `sha256=<hex>`, an HMAC-SHA256 of the raw body.
+pub fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &str,
+) -> Result<(), SignatureError> {
+    let header = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(SignatureError::MissingHeader("X-Hub-Signature-256"))?;
+    let signature = header
+        .strip_prefix("sha256=")
+        .ok_or(SignatureError::Malformed("X-Hub-Signature-256"))?;
+    signature::verify_hmac_sha256_hex(secret.as_bytes(), body, signature)
+}
+
+// Falls back to the status while a run is still going and has no conclusion yet.
+fn outcome(status: &Status, conclusion: &Option<Conclusion>) -> (String, Color) {
+    match conclusion {
+        Some(conclusion) => (format!("{conclusion:?}"), conclusion_color(conclusion)),
+        None => (format!("{status:?}"), Color::Accent),
+    }
+}
+
+fn conclusion_color(conclusion: &Conclusion) -> Color {
+    match conclusion {
+        Conclusion::Success => Color::Good,
+        Conclusion::Failure | Conclusion::TimedOut | Conclusion::StartupFailure => Color::Attention,
+        Conclusion::Cancelled | Conclusion::ActionRequired | Conclusion::Stale => Color::Warning,
+        _ => Color::Default,
+    }
+}
+
+fn deployment_color(state: &DeploymentState) -> Color {
+    match state {
+        DeploymentState::Success => Color::Good,
+        DeploymentState::Failure | DeploymentState::Error => Color::Attention,
+        DeploymentState::Inactive => Color::Warning,
+        _ => Color::Accent,
+    }
+}
+
+fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
+
+impl From<GithubEvent> for AdaptiveCardData {
+    fn from(event: GithubEvent) -> Self {
+        match event {
+            GithubEvent::Ping(data) => {
+                let name = data
+                    .repository
+                    .as_ref()
+                    .map_or("GitHub".to_string(), |r| r.full_name.clone());
+                let mut card = Card::new()
+                    .title(format!("{name} webhook ping"), Color::Accent)
+                    .byline(
+                        &data.sender.login,
+                        data.sender.avatar_url.as_deref(),
+                        format!("Hook {}", data.hook_id),
+                    )
+                    .text(data.zen);
+                if let Some(repository) = data.repository {
+                    card = card.open_url("View on GitHub", repository.html_url);
+                }
+                card.into()
+            }
+            GithubEvent::WorkflowRun(data) => {
+                let run = data.workflow_run;
+                let (outcome, color) = outcome(&run.status, &run.conclusion);
+                let actor = run.actor.as_ref().unwrap_or(&data.sender);
+                let branch = run.head_branch.as_deref().unwrap_or("unknown branch");
+                Card::new()
+                    .title(
+                        format!(
+                            "{} #{} {outcome}",
+                            run.name.as_deref().unwrap_or("Workflow"),
+                            run.run_number
+                        ),
+                        color,
+                    )
+                    .byline(
+                        &actor.login,
+                        actor.avatar_url.as_deref(),
+                        format!("Triggered by {} on {branch}", run.event),
+                    )
+                    .detail(format!("Repository: {}", data.repository.full_name))
+                    .detail(format!("Commit: {}", run.head_sha))
+                    .open_url("View on GitHub", run.html_url)
+                    .into()
+            }
+            GithubEvent::WorkflowJob(data) => {
+                let job = data.workflow_job;
+                let (outcome, color) = outcome(&job.status, &job.conclusion);
+                let mut card = Card::new()
+                    .title(
+                        format!(
+                            "{} / {} {outcome}",
+                            job.workflow_name.as_deref().unwrap_or("Workflow"),
+                            job.name
+                        ),
+                        color,
+                    )
+                    .byline(
+                        &data.sender.login,
+                        data.sender.avatar_url.as_deref(),
+                        format!(
+                            "Job {} on {}",
+                            data.action,
+                            job.head_branch.as_deref().unwrap_or("unknown branch")
+                        ),
+                    )
+                    .detail(format!("Repository: {}", data.repository.full_name))
+                    .detail(format!("Commit: {}", job.head_sha));
+                if let Some(runner) = job.runner_name {
+                    card = card.detail(format!("Runner: {runner}"));
+                }
+                card.open_url("View on GitHub", job.html_url).into()
+            }
+            GithubEvent::CheckSuite(data) => {
+                let suite = data.check_suite;
+                let (outcome, color) = match &suite.conclusion {
+                    Some(conclusion) => (format!("{conclusion:?}"), conclusion_color(conclusion)),
+                    None => (format!("{:?}", suite.status), Color::Accent),
+                };
+                let app = suite.app.as_ref().map_or("Checks", |a| a.name.as_str());
+                Card::new()
+                    .title(
+                        format!(
+                            "{} {app} on {} {outcome}",
+                            data.repository.full_name,
+                            short_sha(&suite.head_sha)
+                        ),
+                        color,
+                    )
+                    .byline(
+                        &data.sender.login,
+                        data.sender.avatar_url.as_deref(),
+                        format!(
+                            "Check suite {} on {}",
+                            data.action,
+                            suite.head_branch.as_deref().unwrap_or("unknown branch")
+                        ),
+                    )
+                    .detail(format!("Repository: {}", data.repository.full_name))
+                    .detail(format!("Commit: {}", suite.head_sha))
+                    .open_url(
+                        "View on GitHub",
+                        format!(
+                            "{}/commit/{}/checks",
+                            data.repository.html_url, suite.head_sha
+                        ),
+                    )
+                    .into()
+            }
+            GithubEvent::PullRequest(data) => {
+                let pr = data.pull_request;
+                let action = if data.action == "closed" && pr.merged {
+                    "merged"
+                } else {
+                    data.action.as_str()
+                };
+                let color = match action {
+                    "merged" => Color::Good,
+                    "closed" => Color::Warning,
+                    _ => Color::Accent,
+                };
+                let draft = if pr.draft { " (draft)" } else { "" };
+                Card::new()
+                    .title(
+                        format!(
+                            "{} #{} {action}: {}{draft}",
+                            data.repository.full_name, data.number, pr.title
+                        ),
+                        color,
+                    )
+                    .byline(
+                        &pr.user.login,
+                        pr.user.avatar_url.as_deref(),
+                        format!(
+                            "Wants to merge {} into {}",
+                            pr.head.branch_ref, pr.base.branch_ref
+                        ),
+                    )
+                    .detail(format!("Repository: {}", data.repository.full_name))
+                    .open_url("View on GitHub", pr.html_url)
+                    .into()
+            }
+            GithubEvent::Release(data) => {
+                let release = data.release;
+                let kind = if release.prerelease {
+                    "Pre-release"
+                } else {
+                    "Release"
+                };
+                let mut card = Card::new()
+                    .title(
+                        format!(
+                            "{} {kind} {} {}",
+                            data.repository.full_name,
+                            release.name.as_deref().unwrap_or(&release.tag_name),
+                            data.action
+                        ),
+                        if data.action == "deleted" {
+                            Color::Warning
+                        } else {
+                            Color::Good
+                        },
+                    )
+                    .byline(
+                        &release.author.login,
+                        release.author.avatar_url.as_deref(),
+                        format!(
+                            "Published {}",
+                            release.published_at.as_deref().unwrap_or("just now")
+                        ),
+                    )
+                    .detail(format!("Tag: {}", release.tag_name));
+                if let Some(notes) = release.body.filter(|b| !b.is_empty()) {
+                    card = card.text(notes);
+                }
+                card.open_url("View on GitHub", release.html_url).into()
+            }
+            GithubEvent::DeploymentStatus(data) => {
+                let status = data.deployment_status;
+                let environment = status
+                    .environment
+                    .as_deref()
+                    .unwrap_or(&data.deployment.environment);
+                let creator = status.creator.as_ref().unwrap_or(&data.sender);
+                let mut card = Card::new()
+                    .title(
+                        format!(
+                            "{} deploy to {environment} {:?}",
+                            data.repository.full_name, status.state
+                        ),
+                        deployment_color(&status.state),
+                    )
+                    .byline(
+                        &creator.login,
+                        creator.avatar_url.as_deref(),
+                        format!("Deploying {}", data.deployment.deployment_ref),
+                    )
+                    .detail(format!("Repository: {}", data.repository.full_name))
+                    .detail(format!("Commit: {}", data.deployment.sha));
+                if let Some(description) = status.description.filter(|d| !d.is_empty()) {
+                    card = card.text(description);
+                }
+                if let Some(url) = status.log_url.or(status.target_url) {
+                    card = card.open_url("View on GitHub", url);
+                }
+                if let Some(url) = status.environment_url.filter(|u| !u.is_empty()) {
+                    card = card.open_url("Open environment", url);
+                }
+                card.into()
+            }
+        }
+    }
+}
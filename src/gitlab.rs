@@ -0,0 +1,336 @@
+use axum::http::HeaderMap;
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::signature::{self, SignatureError};
+
+// GitLab webhook payloads.  GitLab sends the hook type in the X-Gitlab-Event header
+// ("Pipeline Hook", "Job Hook", ...) and the matching `object_kind` in the body, see
+// https://docs.gitlab.com/ee/user/project/integrations/webhook_events.html
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "object_kind", rename_all = "snake_case")]
+pub enum GitlabEvent {
+    Pipeline(PipelineData),
+    // Job Hook payloads still use the old "build" object kind
+    Build(JobData),
+    MergeRequest(MergeRequestData),
+    Deployment(DeploymentData),
+}
+
+// Shared by pipelines, jobs and deployments.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Created,
+    WaitingForResource,
+    Preparing,
+    Pending,
+    Running,
+    Success,
+    Failed,
+    Canceled,
+    Skipped,
+    Manual,
+    Scheduled,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    pub path_with_namespace: String,
+    pub web_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Commit {
+    pub id: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub id: u64,
+    #[serde(rename = "ref")]
+    pub pipeline_ref: String,
+    pub sha: String,
+    pub status: Status,
+    pub source: Option<String>,
+    pub created_at: Option<String>,
+    pub duration: Option<u64>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PipelineJob {
+    pub id: u64,
+    pub stage: String,
+    pub name: String,
+    pub status: Status,
+    #[serde(default)]
+    pub allow_failure: bool,
+    pub failure_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PipelineData {
+    pub object_attributes: Pipeline,
+    pub user: User,
+    pub project: Project,
+    pub commit: Option<Commit>,
+    #[serde(default)]
+    pub builds: Vec<PipelineJob>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobCommit {
+    pub sha: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobRepository {
+    pub name: String,
+    pub homepage: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobData {
+    #[serde(rename = "ref")]
+    pub job_ref: String,
+    pub sha: String,
+    pub build_id: u64,
+    pub build_name: String,
+    pub build_stage: String,
+    pub build_status: Status,
+    pub build_duration: Option<f64>,
+    #[serde(default)]
+    pub build_allow_failure: bool,
+    pub build_failure_reason: Option<String>,
+    pub pipeline_id: u64,
+    pub project_name: String,
+    pub user: User,
+    pub commit: Option<JobCommit>,
+    pub repository: JobRepository,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeRequest {
+    pub iid: u64,
+    pub title: String,
+    pub state: String,
+    pub action: Option<String>,
+    pub source_branch: String,
+    pub target_branch: String,
+    pub url: String,
+    #[serde(default)]
+    pub draft: bool,
+    pub merge_status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeRequestData {
+    pub user: User,
+    pub project: Project,
+    pub object_attributes: MergeRequest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeploymentData {
+    pub status: Status,
+    pub deployment_id: u64,
+    pub deployable_url: Option<String>,
+    pub environment: String,
+    pub project: Project,
+    pub short_sha: String,
+    pub user: User,
+    pub commit_url: Option<String>,
+    pub commit_title: Option<String>,
+    #[serde(rename = "ref")]
+    pub deployment_ref: String,
+}
+
+// X-Gitlab-Token carries the secret token configured on the webhook verbatim.
+pub fn verify_token(headers: &HeaderMap, token: &str) -> Result<(), SignatureError> {
+    let given = headers
+        .get("X-Gitlab-Token")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(SignatureError::MissingHeader("X-Gitlab-Token"))?;
+    if signature::constant_time_eq(token.as_bytes(), given.as_bytes()) {
+        Ok(())
+    } else {
+        Err(SignatureError::Mismatch)
+    }
+}
+
+fn status_color(status: &Status) -> Color {
+    match status {
+        Status::Success => Color::Good,
+        Status::Failed => Color::Attention,
+        Status::Canceled | Status::Manual => Color::Warning,
+        _ => Color::Accent,
+    }
+}
+
+fn pipeline_card(data: PipelineData) -> AdaptiveCardData {
+    let pipeline = data.object_attributes;
+    let pipeline_url = pipeline.url.clone().unwrap_or(format!(
+        "{}/-/pipelines/{}",
+        data.project.web_url, pipeline.id
+    ));
+    let mut card = Card::new()
+        .title(
+            format!(
+                "{} pipeline #{} {:?}",
+                data.project.path_with_namespace, pipeline.id, pipeline.status
+            ),
+            status_color(&pipeline.status),
+        )
+        .byline(
+            &data.user.name,
+            data.user.avatar_url.as_deref(),
+            format!(
+                "Triggered by {} on {}",
+                pipeline.source.as_deref().unwrap_or("push"),
+                pipeline.pipeline_ref
+            ),
+        )
+        .detail(format!("Repository: {}", data.project.web_url))
+        .detail(format!("Commit: {}", pipeline.sha));
+    if let Some(title) = data.commit.and_then(|c| c.title) {
+        card = card.text(title);
+    }
+    let failed: Vec<(&str, String)> = data
+        .builds
+        .iter()
+        .filter(|job| matches!(job.status, Status::Failed))
+        .map(|job| {
+            let mut value = job.name.clone();
+            if let Some(reason) = &job.failure_reason {
+                value.push_str(&format!(" ({reason})"));
+            }
+            if job.allow_failure {
+                value.push_str(" [allowed to fail]");
+            }
+            (job.stage.as_str(), value)
+        })
+        .collect();
+    if !failed.is_empty() {
+        card = card.text("**Failed jobs**").facts(failed);
+    }
+    card.open_url("View pipeline", pipeline_url).into()
+}
+
+fn job_card(data: JobData) -> AdaptiveCardData {
+    let mut card = Card::new()
+        .title(
+            format!(
+                "{} {} / {} {:?}",
+                data.project_name, data.build_stage, data.build_name, data.build_status
+            ),
+            status_color(&data.build_status),
+        )
+        .byline(
+            &data.user.name,
+            data.user.avatar_url.as_deref(),
+            format!("Pipeline #{} on {}", data.pipeline_id, data.job_ref),
+        )
+        .detail(format!("Repository: {}", data.repository.homepage))
+        .detail(format!("Commit: {}", data.sha));
+    if let Some(reason) = data.build_failure_reason.filter(|r| r != "unknown_failure") {
+        card = card.detail(format!("Failure reason: {reason}"));
+    }
+    card.open_url(
+        "View job",
+        format!("{}/-/jobs/{}", data.repository.homepage, data.build_id),
+    )
+    .open_url(
+        "View pipeline",
+        format!(
+            "{}/-/pipelines/{}",
+            data.repository.homepage, data.pipeline_id
+        ),
+    )
+    .into()
+}
+
+fn merge_request_card(data: MergeRequestData) -> AdaptiveCardData {
+    let mr = data.object_attributes;
+    let action = mr.action.as_deref().unwrap_or(&mr.state);
+    let color = match action {
+        "merge" | "approved" => Color::Good,
+        "close" => Color::Warning,
+        _ => Color::Accent,
+    };
+    let draft = if mr.draft { " (draft)" } else { "" };
+    Card::new()
+        .title(
+            format!(
+                "{} !{} {action}: {}{draft}",
+                data.project.path_with_namespace, mr.iid, mr.title
+            ),
+            color,
+        )
+        .byline(
+            &data.user.name,
+            data.user.avatar_url.as_deref(),
+            format!(
+                "Wants to merge {} into {}",
+                mr.source_branch, mr.target_branch
+            ),
+        )
+        .detail(format!("Repository: {}", data.project.web_url))
+        .open_url("View merge request", mr.url)
+        .into()
+}
+
+fn deployment_card(data: DeploymentData) -> AdaptiveCardData {
+    let mut card = Card::new()
+        .title(
+            format!(
+                "{} deploy to {} {:?}",
+                data.project.path_with_namespace, data.environment, data.status
+            ),
+            status_color(&data.status),
+        )
+        .byline(
+            &data.user.name,
+            data.user.avatar_url.as_deref(),
+            format!("Deploying {}", data.deployment_ref),
+        )
+        .detail(format!("Repository: {}", data.project.web_url))
+        .detail(format!("Commit: {}", data.short_sha));
+    if let Some(title) = data.commit_title {
+        card = card.text(title);
+    }
+    if let Some(url) = data.deployable_url {
+        card = card.open_url("View job", url);
+    }
+    card = card.open_url(
+        "View environments",
+        format!("{}/-/environments", data.project.web_url),
+    );
+    card.into()
+}
+
+impl From<GitlabEvent> for AdaptiveCardData {
+    fn from(event: GitlabEvent) -> Self {
+        match event {
+            GitlabEvent::Pipeline(data) => pipeline_card(data),
+            GitlabEvent::Build(data) => job_card(data),
+            GitlabEvent::MergeRequest(data) => merge_request_card(data),
+            GitlabEvent::Deployment(data) => deployment_card(data),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+
+// Grafana webhook contact point payload.  Unified alerting sends an Alertmanager-style
+// `alerts` array; legacy dashboard alerts send `evalMatches`, `ruleUrl` and `imageUrl`
+// instead, so both sets of fields are optional, see the webhook notifier among the contact
+// point integrations in https://grafana.com/docs/grafana/latest/alerting/
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaData {
+    pub state: Option<GrafanaState>,
+    pub status: Option<String>,
+    pub title: Option<String>,
+    pub message: Option<String>,
+    pub rule_name: Option<String>,
+    pub rule_url: Option<String>,
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub eval_matches: Vec<EvalMatch>,
+    #[serde(default)]
+    pub alerts: Vec<GrafanaAlert>,
+    #[serde(rename = "externalURL")]
+    pub external_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrafanaState {
+    Alerting,
+    Ok,
+    NoData,
+    Pending,
+    Paused,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalMatch {
+    pub metric: String,
+    pub value: Option<serde_json::Value>,
+    #[serde(default)]
+    pub tags: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaAlert {
+    pub status: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    pub values: Option<BTreeMap<String, serde_json::Value>>,
+    pub value_string: Option<String>,
+    #[serde(rename = "generatorURL")]
+    pub generator_url: Option<String>,
+    #[serde(rename = "silenceURL")]
+    pub silence_url: Option<String>,
+    #[serde(rename = "dashboardURL")]
+    pub dashboard_url: Option<String>,
+    #[serde(rename = "panelURL")]
+    pub panel_url: Option<String>,
+    #[serde(rename = "imageURL")]
+    pub image_url: Option<String>,
+}
+
+// Most alerts to list values for before the card gets unwieldy.
+const MAX_LISTED_ALERTS: usize = 10;
+
+fn state_color(state: &GrafanaState) -> Color {
+    match state {
+        GrafanaState::Alerting => Color::Attention,
+        GrafanaState::Ok => Color::Good,
+        GrafanaState::NoData | GrafanaState::Pending => Color::Warning,
+        _ => Color::Default,
+    }
+}
+
+fn format_value(value: Option<&serde_json::Value>) -> String {
+    match value {
+        Some(serde_json::Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+impl From<GrafanaData> for AdaptiveCardData {
+    fn from(data: GrafanaData) -> Self {
+        let state = data.state.unwrap_or(match data.status.as_deref() {
+            Some("resolved") => GrafanaState::Ok,
+            Some("firing") => GrafanaState::Alerting,
+            _ => GrafanaState::Unknown,
+        });
+        let title = data
+            .title
+            .or(data.rule_name)
+            .unwrap_or("Grafana alert".to_string());
+        // Legacy titles already lead with the state, e.g. "[Alerting] CPU high"
+        let title = if title.starts_with('[') {
+            title
+        } else {
+            format!("[{state:?}] {title}")
+        };
+        let mut card = Card::new().title(title, state_color(&state));
+        if let Some(message) = data.message.filter(|m| !m.is_empty()) {
+            card = card.text(message);
+        }
+
+        let mut values: Vec<(String, String)> = data
+            .eval_matches
+            .iter()
+            .map(|m| (m.metric.clone(), format_value(m.value.as_ref())))
+            .collect();
+        for alert in data.alerts.iter().take(MAX_LISTED_ALERTS) {
+            let name = alert
+                .labels
+                .get("alertname")
+                .map_or(alert.status.as_str(), |s| s.as_str());
+            match &alert.values {
+                Some(alert_values) if !alert_values.is_empty() => values.extend(
+                    alert_values
+                        .iter()
+                        .map(|(key, value)| (format!("{name} {key}"), format_value(Some(value)))),
+                ),
+                _ => values.push((
+                    name.to_string(),
+                    alert.value_string.clone().unwrap_or(alert.status.clone()),
+                )),
+            }
+        }
+        card = card.facts(values);
+
+        let image_url = data
+            .image_url
+            .or(data.alerts.iter().find_map(|a| a.image_url.clone()));
+        if let Some(url) = image_url.filter(|u| !u.is_empty()) {
+            card = card.image(url, "Panel snapshot");
+        }
+
+        let first = data.alerts.first();
+        if let Some(url) = data
+            .rule_url
+            .or(first.and_then(|a| a.panel_url.clone()))
+            .or(first.and_then(|a| a.dashboard_url.clone()))
+            .filter(|u| !u.is_empty())
+        {
+            card = card.open_url("Open in Grafana", url);
+        }
+        if let Some(url) = first
+            .and_then(|a| a.generator_url.clone())
+            .filter(|u| !u.is_empty())
+        {
+            card = card.open_url("View alert rule", url);
+        }
+        if matches!(state, GrafanaState::Alerting) {
+            if let Some(url) = first.and_then(|a| a.silence_url.clone()) {
+                card = card.open_url("Silence", url);
+            }
+        }
+        card.into()
+    }
+}
@@ -0,0 +1,6 @@
+//! The Adaptive Card model, builder and checks the adapter's sources are written with, so other
+//! crates can produce cards the same way, and the legacy MessageCard they can be rendered as.
+pub mod builder;
+pub mod card;
+pub mod message_card;
+pub mod validate;
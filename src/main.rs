@@ -1,12 +1,33 @@
 mod adaptive_card;
+mod alertmanager;
 mod buildkite;
+mod eventbridge;
+mod generic;
+mod github;
+mod gitlab;
+mod grafana;
+mod opsgenie;
+mod pagerduty;
+mod queue;
+mod rate_limit;
+mod sentry;
+mod signature;
+mod slack;
 mod snitch;
+mod sns;
+mod target;
+mod template;
 
-use std::{collections::HashMap, env};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    time::Duration,
+};
 
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{Path, Query, State},
+    http::HeaderMap,
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
@@ -15,19 +36,99 @@ use axum::{
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_secretsmanager::Client;
 
-use buildkite::BuildData;
+use alertmanager::AlertmanagerData;
+use buildkite::BuildkiteEvent;
+use eventbridge::EventBridgeEvent;
+use github::GithubEvent;
+use gitlab::GitlabEvent;
+use grafana::GrafanaData;
+use opsgenie::OpsgenieData;
+use pagerduty::PagerDutyData;
 use reqwest::StatusCode;
+use sentry::SentryEvent;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use slack::SlackMessage;
 use snitch::DmsData;
+use sns::{MessageType, SnsMessage};
+use target::RequestFormat;
+use tokio::sync::broadcast;
 
 use crate::adaptive_card::AdaptiveCardData;
+use lessons::message_card::MessageCard;
+use lessons::validate::{self, CardError};
 
-const BASE_URL: &'static str = "https://<yoursite>.webhook.office.com/webhookb2/";
+const BASE_URL: &str = "https://<yoursite>.webhook.office.com/webhookb2/";
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, Deserialize)]
 struct TeamsChannelUrl {
+    #[serde(skip)]
     name: String,
     url: String,
+    // Buildkite webhook token and/or signing secret for pipelines posting to this channel
+    buildkite_token: Option<String>,
+    buildkite_signing_secret: Option<String>,
+    // Secret for X-Hub-Signature-256; without it GitHub webhooks need the apiKey instead
+    github_secret: Option<String>,
+    // Secret token GitLab sends as X-Gitlab-Token; without it the apiKey is required instead
+    gitlab_token: Option<String>,
+    // Client secret of the Sentry integration, used to check Sentry-Hook-Signature
+    sentry_secret: Option<String>,
+    // PagerDuty webhook subscription secret for X-PagerDuty-Signature; without it the apiKey
+    // is required instead
+    pagerduty_secret: Option<String>,
+    #[serde(default)]
+    format: MessageFormat,
+    // What `url` expects in the request body; by default a workflow for Workflows URLs and a
+    // connector otherwise
+    request_format: Option<RequestFormat>,
+    // The Office 365 connector URL of a channel that has moved to Workflows.  /webhookb2
+    // requests for its path are delivered to `url` instead, so senders don't need changing.
+    connector_url: Option<String>,
+    #[serde(default)]
+    delivery_mode: DeliveryMode,
+}
+
+impl TeamsChannelUrl {
+    fn request_format(&self) -> RequestFormat {
+        self.request_format
+            .unwrap_or_else(|| RequestFormat::for_url(&self.url))
+    }
+}
+
+// How notifications are rendered for a channel.  Sources without a plain-text rendering
+// always send an Adaptive Card, or its MessageCard rendering.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum MessageFormat {
+    #[default]
+    AdaptiveCard,
+    Text,
+    // The legacy Office 365 connector card, for channels and consumers that can't show
+    // Adaptive Cards
+    MessageCard,
+}
+
+// What webhooks for a channel wait for before they are answered.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum DeliveryMode {
+    // The first attempt at sending the message, so the sender hears whether it got through.
+    // Holds the request open for up to SYNC_TIMEOUT, longer than that when the channel is
+    // busy, so channels have to ask for it.
+    Sync,
+    // Only the message being queued, answered with 202 Accepted
+    #[default]
+    Async,
+}
+
+// Entries in webhooks.json are either a bare webhook URL or an object with per-channel
+// settings, e.g. {"url": "...", "buildkite_signing_secret": "..."}
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelEntry {
+    Url(String),
+    Config(TeamsChannelUrl),
 }
 
 #[derive(Clone)]
@@ -36,9 +137,12 @@ struct AppState {
     channels: Vec<TeamsChannelUrl>,
     base_url: String,
     api_key: String,
+    sns_certificates: sns::CertificateCache,
+    mappings: HashMap<String, generic::Mapping>,
+    queue: queue::Queue,
 }
 
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
 struct QueryParams {
     #[serde(rename = "apiKey")]
     api_key: Option<String>,
@@ -49,6 +153,11 @@ struct QueryParams {
 struct HealthCheckResponse {
     status: String,
     url_count: String,
+    // Messages waiting to be sent
+    pending: u64,
+    // The same per channel, only with the apiKey as it names every channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backlog: Option<BTreeMap<String, u64>>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -56,53 +165,249 @@ struct PostData {
     text: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
+// Payloads accepted on /webhook and /webhookb2.  See parse_webhook_data for how the source
+// is picked; PostData is only a fallback for senders that do not identify themselves.
+#[derive(Debug)]
 enum WebhookData {
-    TypeA(BuildData),
+    // The event, and the body it was parsed from
+    TypeA(Box<BuildkiteEvent>, Value),
     TypeB(PostData),
+    Sentry(Box<SentryEvent>),
+    Slack(SlackMessage),
+}
+
+// How long a sync webhook waits for the first attempt at its message.  Senders such as GitHub
+// give up after 10 seconds, so it is answered as queued after that.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(8);
+
+// The response to a webhook whose message was queued.
+#[derive(Debug, Serialize)]
+struct DeliveryResult {
+    id: i64,
+    status: queue::Status,
+    // The response of Teams to the last attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl IntoResponse for DeliveryResult {
+    fn into_response(self) -> Response {
+        // A delivery that failed but will be retried is still accepted: senders that retry
+        // on a 5xx would otherwise post it again, and Teams would get it twice
+        let status = match self.status {
+            queue::Status::Pending => StatusCode::ACCEPTED,
+            queue::Status::Delivered => StatusCode::OK,
+            queue::Status::DeadLettered => StatusCode::BAD_GATEWAY,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+#[derive(Debug)]
+pub enum DeliveryError {
+    Invalid(CardError),
+    Queue(rusqlite::Error),
+}
+
+// Long text and fact lists are cut down to fit the message size limit when
+// TRUNCATE_LONG_CARDS is set, instead of the message being refused.
+fn truncate_long_cards() -> bool {
+    env::var("TRUNCATE_LONG_CARDS").is_ok_and(|v| v == "true" || v == "1")
 }
 
-pub async fn make_post_request<T>(url: String, data: T) -> Result<(), reqwest::Error>
+// The request body to send `target` for a message, once it has been checked.
+fn render_message<T>(target: &TeamsChannelUrl, data: T) -> Result<Value, CardError>
 where
-    T: Serialize + std::fmt::Debug,
+    T: Serialize,
 {
-    let client = reqwest::Client::new();
+    // By name, as Workflows URLs hold their sig secret
+    let name = &target.name;
+    let format = target.request_format();
+    let mut message = serde_json::to_value(&data).expect("messages serialize to JSON");
+    if format != RequestFormat::Connector {
+        message = target::card_message(message);
+    }
+    if truncate_long_cards() && validate::truncate(&mut message) {
+        println!("Shortened the message to {name} to fit the size limit");
+    }
+    if let Err(err) = validate::validate(&message) {
+        println!("Not sending to {name}: {err}");
+        return Err(err);
+    }
+
+    // Cards are checked as Adaptive Cards, whatever they are sent as
+    match target.format {
+        MessageFormat::MessageCard => {
+            let body = MessageCard::from_message(&message)
+                .map(|card| serde_json::to_value(card).expect("MessageCards serialize to JSON"))
+                .map_err(|reason| CardError {
+                    violations: vec![validate::Violation::NoMessageCard { reason }],
+                })
+                // A MessageCard can come out larger, e.g. with the title repeated as its summary
+                .and_then(|body| validate::check_size(&body).map(|_| body));
+            if let Err(err) = &body {
+                println!("Not sending to {name}: {err}");
+            }
+            body
+        }
+        _ => Ok(target::request_body(format, message)),
+    }
+}
 
-    println!("Ready to send post request to {:?}: {:?}", url, data);
-    // Send the POST request
-    let res = client.post(url).json(&data).send().await?;
+// Checks a message from `source` and queues it for delivery to `target`, along with the
+// `payload` it was rendered from when it can be rendered again for a replay.
+async fn enqueue<T>(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    source: &str,
+    payload: Option<Value>,
+    data: T,
+) -> Result<i64, DeliveryError>
+where
+    T: Serialize,
+{
+    let body = render_message(target, data).map_err(DeliveryError::Invalid)?;
+    let id = state
+        .queue
+        .enqueue(&target.name, source, &target.url, &body, payload.as_ref())
+        .await
+        .map_err(DeliveryError::Queue)?;
+    println!("Queued delivery {id} to {}", target.name);
+    Ok(id)
+}
 
-    // Check the response status and optionally handle the response body
-    if res.status().is_success() {
-        println!("Request successful.");
+// Queues a message and answers the webhook: as soon as it is queued for async channels, or
+// with how the first attempt at sending it went for sync ones.  Messages that aren't valid
+// cards, or that can't be stored, are refused instead.
+async fn deliver<T>(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    source: &str,
+    payload: Option<Value>,
+    data: T,
+) -> Result<Response, Response>
+where
+    T: Serialize,
+{
+    let mut attempts = state.queue.subscribe();
+    let id = match enqueue(state, target, source, payload, data).await {
+        Ok(id) => id,
+        Err(DeliveryError::Invalid(err)) => return Err(invalid_card(err)),
+        Err(DeliveryError::Queue(err)) => {
+            println!("Could not queue the message for {}: {err}", target.name);
+            return Err(Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(Body::from("Unable to queue the message, try again later\n"))
+                .unwrap());
+        }
+    };
+    let mut result = DeliveryResult {
+        id,
+        status: queue::Status::Pending,
+        upstream_status: None,
+        error: None,
+    };
+    if target.delivery_mode == DeliveryMode::Async {
+        return Ok(result.into_response());
+    }
+    let first_attempt = async {
+        loop {
+            match attempts.recv().await {
+                Ok(attempt) if attempt.id == id => match attempt.outcome {
+                    // Not an attempt, the message just waits longer
+                    queue::Outcome::Throttled { .. } => {}
+                    _ => return Some(attempt),
+                },
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    };
+    // Still queued when it takes too long
+    if let Ok(Some(attempt)) = tokio::time::timeout(SYNC_TIMEOUT, first_attempt).await {
+        result.status = attempt.status;
+        match attempt.outcome {
+            queue::Outcome::Delivered(status) => result.upstream_status = Some(status),
+            queue::Outcome::Retry { status, error } => {
+                result.upstream_status = status;
+                result.error = Some(error);
+            }
+            queue::Outcome::Failed { status, error } => {
+                result.upstream_status = Some(status);
+                result.error = Some(error);
+            }
+            queue::Outcome::Throttled { .. } => {}
+        }
+    }
+    let response = result.into_response();
+    if response.status() == StatusCode::BAD_GATEWAY {
+        Err(response)
     } else {
-        println!("Request failed with status: {}", res.status());
+        Ok(response)
     }
+}
 
-    Ok(())
+async fn process_standard_webhook(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    data_object: PostData,
+) -> Result<Response, Response> {
+    deliver(state, target, "text", None, data_object).await
 }
 
-async fn process_standard_webhook(url: String, data_object: PostData) {
-    let _res = make_post_request(url, data_object).await;
+async fn process_adaptivecard_webhook(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    data_object: BuildkiteEvent,
+    payload: Value,
+) -> Result<Response, Response> {
+    println!("Got Buildkite {} webhook", data_object.name());
+    let adaptive_card_data = adaptive_card::buildkite_card(&data_object, payload.clone());
+    deliver(
+        state,
+        target,
+        "buildkite",
+        Some(payload),
+        adaptive_card_data,
+    )
+    .await
 }
 
-async fn process_adaptivecard_webhook(url: String, data_object: BuildData) {
-    let adaptive_card_data: AdaptiveCardData = data_object.into();
-    let _res = make_post_request(url, adaptive_card_data).await;
+async fn process_slack_webhook(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    data_object: SlackMessage,
+) -> Result<Response, Response> {
+    println!("Got Slack-format webhook");
+    deliver(
+        state,
+        target,
+        "slack",
+        None,
+        AdaptiveCardData::from(data_object),
+    )
+    .await
 }
 
-fn get_webhook_url(channels: Vec<TeamsChannelUrl>, channel: String) -> String {
-    channels
-        .iter()
-        .find(|c| c.name == channel)
-        .map_or(format!("No matching URL found for {channel}."), |c| {
-            c.url.clone()
-        })
+async fn process_sentry_webhook(
+    state: &AppState,
+    target: &TeamsChannelUrl,
+    data_object: SentryEvent,
+) -> Result<Response, Response> {
+    println!("Got Sentry {} webhook", data_object.name());
+    deliver(
+        state,
+        target,
+        "sentry",
+        None,
+        AdaptiveCardData::from(data_object),
+    )
+    .await
 }
 
 fn build_dms_post_data(data_object: DmsData) -> PostData {
-    println!("Got DMS webhook");
     let snitch_id = data_object.data.snitch.token;
     let snitch_name = data_object.data.snitch.name;
     let href =
@@ -110,45 +415,204 @@ fn build_dms_post_data(data_object: DmsData) -> PostData {
     let data_string = match data_object.snitch_type {
         snitch::SnitchType::Missing => format!("? {href} is missing"),
         snitch::SnitchType::Reporting => format!("✓ {href} is reporting."),
+        snitch::SnitchType::Errored => format!("! {href} has errored."),
+        snitch::SnitchType::Failed => format!("✗ {href} has failed."),
         snitch::SnitchType::Paused => format!("P {href} is paused."),
+        snitch::SnitchType::Other(_) => {
+            format!("{href} {}.", data_object.snitch_type.description())
+        }
     };
     PostData { text: data_string }
 }
 
+// The DMS message for `target`: a card laid out from `payload`, the webhook body `data` was
+// parsed from, or the old one-line text for text channels.
+fn dms_message(target: &TeamsChannelUrl, data: DmsData, payload: Value) -> Value {
+    let message = match target.format {
+        MessageFormat::Text => serde_json::to_value(build_dms_post_data(data)),
+        MessageFormat::AdaptiveCard | MessageFormat::MessageCard => {
+            serde_json::to_value(snitch::dms_card(&data, payload))
+        }
+    };
+    message.expect("messages serialize to JSON")
+}
+
+// Pick the source from its identifying header, then from the body: Sentry sends
+// Sentry-Hook-Resource, Buildkite names the event in X-Buildkite-Event and/or an `event`
+// field holding one of its event names, Slack-format messages have attachments or blocks,
+// and anything else must be a plain {"text": ...} message.  A payload is parsed strictly as
+// the source it claims to be, so a bad Sentry or Buildkite body is rejected rather than being
+// mistaken for another source.
+fn parse_webhook_data(
+    headers: &HeaderMap,
+    mut data: serde_json::Value,
+) -> Result<WebhookData, serde_json::Error> {
+    if let Some(resource) = headers
+        .get("Sentry-Hook-Resource")
+        .and_then(|v| v.to_str().ok())
+    {
+        return SentryEvent::parse(resource, data).map(|e| WebhookData::Sentry(Box::new(e)));
+    }
+    let header_event = headers
+        .get("X-Buildkite-Event")
+        .and_then(|v| v.to_str().ok());
+    if let (Some(event), Some(object)) = (header_event, data.as_object_mut()) {
+        object
+            .entry("event")
+            .or_insert_with(|| serde_json::Value::String(event.to_string()));
+    }
+    // Other senders may have an `event` field of their own, so without the header it has to
+    // name a Buildkite event
+    let from_buildkite = header_event.is_some()
+        || data
+            .get("event")
+            .and_then(Value::as_str)
+            .is_some_and(BuildkiteEvent::is_known);
+    if from_buildkite {
+        serde_json::from_value(data.clone()).map(|e| WebhookData::TypeA(Box::new(e), data))
+    } else if data.get("attachments").is_some() || data.get("blocks").is_some() {
+        serde_json::from_value(data).map(WebhookData::Slack)
+    } else {
+        serde_json::from_value(data).map(WebhookData::TypeB)
+    }
+}
+
+// Authenticate sources that have per-channel secrets.  Plain text and Slack-format payloads
+// carry no signature, so they are refused once a channel has a Buildkite or Sentry secret.
+fn verify_source_request(
+    headers: &HeaderMap,
+    body: &[u8],
+    data: &WebhookData,
+    channel: Option<&TeamsChannelUrl>,
+) -> Result<(), signature::SignatureError> {
+    let Some(channel) = channel else {
+        return Ok(());
+    };
+    match data {
+        WebhookData::TypeA(..) => buildkite::verify_request(
+            headers,
+            body,
+            channel.buildkite_token.as_deref(),
+            channel.buildkite_signing_secret.as_deref(),
+            signature::unix_now(),
+        )
+        .inspect_err(|err| println!("Rejecting Buildkite webhook for {}: {err}", channel.name)),
+        WebhookData::Sentry(_) => match &channel.sentry_secret {
+            Some(secret) => sentry::verify_signature(headers, body, secret).inspect_err(|err| {
+                println!("Rejecting Sentry webhook for {}: {err}", channel.name)
+            }),
+            None => Ok(()),
+        },
+        WebhookData::TypeB(_) | WebhookData::Slack(_) => {
+            let signed = channel.buildkite_token.is_some()
+                || channel.buildkite_signing_secret.is_some()
+                || channel.sentry_secret.is_some();
+            if signed {
+                println!("Rejecting unsigned webhook for {}", channel.name);
+                return Err(signature::SignatureError::Unsigned);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn unauthorized(err: signature::SignatureError) -> Response {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .body(Body::from(format!("Unauthorized: {err}\n")))
+        .unwrap()
+}
+
+fn api_key_mismatch() -> Response {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from("API Key mismatch\n"))
+        .unwrap()
+}
+
+fn unknown_channel(channel: &str) -> Response {
+    println!("No channel named {channel}.");
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from("Unknown channel\n"))
+        .unwrap()
+}
+
+fn unprocessable(err: serde_json::Error) -> Response {
+    println!("Could not parse webhook payload: {err}");
+    Response::builder()
+        .status(StatusCode::UNPROCESSABLE_ENTITY)
+        .body(Body::from(format!(
+            "Unable to parse webhook payload: {err}\n"
+        )))
+        .unwrap()
+}
+
+fn invalid_card(err: CardError) -> Response {
+    (StatusCode::UNPROCESSABLE_ENTITY, Json(err)).into_response()
+}
+
+// Paths /webhookb2 takes requests for: those of the channels' connector URLs, including the
+// old ones of channels moved to Workflows.
 fn teams_urls_to_array(channels: &[TeamsChannelUrl]) -> Vec<String> {
     channels
-        .into_iter()
-        .map(|channel| {
-            let parts: Vec<&str> = channel.url.split(BASE_URL).collect();
-            parts[1].to_string()
-        })
+        .iter()
+        .flat_map(|channel| [Some(channel.url.as_str()), channel.connector_url.as_deref()])
+        .flatten()
+        .filter_map(target::connector_path)
+        .map(String::from)
         .collect()
 }
 
+// Where a /webhookb2 request for `path` goes: the channel's Workflows URL once it has moved,
+// otherwise the connector it was addressed to.
+fn webhookb2_target(state: &AppState, path: &str) -> TeamsChannelUrl {
+    let channel = state.channels.iter().find(|c| {
+        target::connector_path(&c.url) == Some(path)
+            || c.connector_url.as_deref().and_then(target::connector_path) == Some(path)
+    });
+    match channel {
+        Some(channel) if target::connector_path(&channel.url) != Some(path) => channel.clone(),
+        _ => TeamsChannelUrl {
+            url: state.base_url.to_owned() + path,
+            ..channel.cloned().unwrap_or_default()
+        },
+    }
+}
+
 async fn handle_webhook(
     Query(params): Query<QueryParams>,
     State(state): State<AppState>,
-    Json(data): Json<WebhookData>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
+    let data = serde_json::from_slice(&body)
+        .and_then(|data| parse_webhook_data(&headers, data))
+        .map_err(unprocessable)?;
     let key = params.api_key.unwrap_or("none".to_string());
     let chan = params.channel.unwrap_or("none".to_string());
-    let url = get_webhook_url(state.channels, chan);
     if key == state.api_key {
         println!("API Key matches. Processing...");
+        let channel = state.channels.iter().find(|c| c.name == chan);
+        verify_source_request(&headers, &body, &data, channel).map_err(unauthorized)?;
+        let Some(target) = channel else {
+            return Err(unknown_channel(&chan));
+        };
         match data {
-            WebhookData::TypeA(build_data) => process_adaptivecard_webhook(url, build_data).await,
-            //_ => return Err(StatusCode::BAD_REQUEST),
-            _ => {
-                return Err(Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from("Invalid unsupported data type."))
-                    .unwrap())
+            WebhookData::TypeA(build_data, payload) => {
+                process_adaptivecard_webhook(&state, target, *build_data, payload).await
+            }
+            WebhookData::Sentry(sentry_data) => {
+                process_sentry_webhook(&state, target, *sentry_data).await
             }
+            WebhookData::Slack(slack_data) => {
+                process_slack_webhook(&state, target, slack_data).await
+            }
+            _ => Err(Response::builder()
+                .status(StatusCode::UNPROCESSABLE_ENTITY)
+                .body(Body::from("Invalid unsupported data type.\n"))
+                .unwrap()),
         }
-        Ok(Response::builder()
-            .status(200)
-            .body(Body::from("Webhook processed\n"))
-            .unwrap())
     } else {
         Err(Response::builder()
             .status(StatusCode::FORBIDDEN)
@@ -160,47 +624,64 @@ async fn handle_webhook(
 async fn handle_webhookb2(
     State(state): State<AppState>,
     Path(webhook_path): Path<String>,
-    Json(data): Json<WebhookData>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
     println!("Processing webhookb2 path");
-    let whitelist = state.whitelist;
+    let data =
+        match serde_json::from_slice(&body).and_then(|data| parse_webhook_data(&headers, data)) {
+            Ok(data) => data,
+            Err(err) => return unprocessable(err),
+        };
     println!("Got a web call");
-    if whitelist.contains(&webhook_path.to_string()) {
-        println!("Path matches whitelist. Processing...");
-        // If we have a channel=asfgasg on the query, prefer that
-        let url = state.base_url.to_owned() + &webhook_path;
-
-        match data {
-            WebhookData::TypeA(build_data) => process_adaptivecard_webhook(url, build_data).await,
-            WebhookData::TypeB(post_data) => process_standard_webhook(url, post_data).await,
-        }
-    } else {
+    if !state.whitelist.contains(&webhook_path.to_string()) {
         println!("Path {} is not in whitelist.", webhook_path);
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Unknown webhook path\n"))
+            .unwrap();
     }
-    // TODO: I should return different responses probably, move this into the if blocks
-    Response::builder()
-        .status(200)
-        .body(Body::from("Webhook processed\n"))
-        .unwrap()
+    println!("Path matches whitelist. Processing...");
+    let target = webhookb2_target(&state, &webhook_path);
+    if let Err(err) = verify_source_request(&headers, &body, &data, Some(&target)) {
+        return unauthorized(err);
+    }
+
+    let delivered = match data {
+        WebhookData::TypeA(build_data, payload) => {
+            process_adaptivecard_webhook(&state, &target, *build_data, payload).await
+        }
+        WebhookData::TypeB(post_data) => process_standard_webhook(&state, &target, post_data).await,
+        WebhookData::Sentry(sentry_data) => {
+            process_sentry_webhook(&state, &target, *sentry_data).await
+        }
+        WebhookData::Slack(slack_data) => process_slack_webhook(&state, &target, slack_data).await,
+    };
+    delivered.unwrap_or_else(|response| response)
 }
 
 async fn handle_webhook_dms(
     State(state): State<AppState>,
     Query(params): Query<QueryParams>,
     Path(webhook_path): Path<String>,
-    Json(data): Json<DmsData>,
+    Json(payload): Json<Value>,
 ) -> impl IntoResponse {
     let key = params.api_key.unwrap_or("none".to_string());
     if key == state.api_key {
         println!("API Key matches. Processing...");
-        let post_data = build_dms_post_data(data);
         let channel = webhook_path.rsplit_once('/').unwrap().1;
-        let _res =
-            make_post_request(get_webhook_url(state.channels, channel.into()), post_data).await;
-        Response::builder()
-            .status(200)
-            .body(Body::from("Webhook processed\n"))
-            .unwrap()
+        let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+            return unknown_channel(channel);
+        };
+        println!("Got DMS webhook");
+        let data = match serde_json::from_value(payload.clone()) {
+            Ok(data) => data,
+            Err(err) => return unprocessable(err),
+        };
+        let message = dms_message(target, data, payload.clone());
+        deliver(&state, target, "dms", Some(payload), message)
+            .await
+            .unwrap_or_else(|response| response)
     } else {
         Response::builder()
             .status(403)
@@ -209,22 +690,506 @@ async fn handle_webhook_dms(
     }
 }
 
-async fn handle_health_check(State(state): State<AppState>) -> impl IntoResponse {
+async fn handle_webhook_github(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    match &target.github_secret {
+        Some(secret) => github::verify_signature(&headers, &body, secret)
+            .inspect_err(|err| println!("Rejecting GitHub webhook for {channel}: {err}"))
+            .map_err(unauthorized)?,
+        None if params.api_key.as_deref() == Some(state.api_key.as_str()) => {}
+        None => return Err(api_key_mismatch()),
+    }
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("none");
+    println!("Got GitHub {event} webhook");
+    let data = GithubEvent::parse(event, &body).map_err(unprocessable)?;
+    deliver(&state, target, "github", None, AdaptiveCardData::from(data)).await
+}
+
+async fn handle_webhook_gitlab(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    headers: HeaderMap,
+    Json(data): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    match &target.gitlab_token {
+        Some(token) => gitlab::verify_token(&headers, token)
+            .inspect_err(|err| println!("Rejecting GitLab webhook for {channel}: {err}"))
+            .map_err(unauthorized)?,
+        None if params.api_key.as_deref() == Some(state.api_key.as_str()) => {}
+        None => return Err(api_key_mismatch()),
+    }
+    println!(
+        "Got GitLab {} webhook",
+        headers
+            .get("X-Gitlab-Event")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("unknown")
+    );
+    let data: GitlabEvent = serde_json::from_value(data).map_err(unprocessable)?;
+    deliver(&state, target, "gitlab", None, AdaptiveCardData::from(data)).await
+}
+
+// Alertmanager webhook_config receiver.  The channel comes from the `channel` query parameter,
+// or failing that from the group's teams_channel label.
+async fn handle_webhook_alertmanager(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Json(data): Json<AlertmanagerData>,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(channel) = params.channel.or(data.channel_label().cloned()) else {
+        return Err(unknown_channel("(none)"));
+    };
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    println!(
+        "Got Alertmanager {:?} webhook for {}",
+        data.status, data.receiver
+    );
+    deliver(
+        &state,
+        target,
+        "alertmanager",
+        None,
+        AdaptiveCardData::from(data),
+    )
+    .await
+}
+
+async fn handle_webhook_grafana(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    Json(data): Json<GrafanaData>,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    println!("Got Grafana webhook: {:?}", data.title);
+    deliver(
+        &state,
+        target,
+        "grafana",
+        None,
+        AdaptiveCardData::from(data),
+    )
+    .await
+}
+
+async fn handle_webhook_pagerduty(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    match &target.pagerduty_secret {
+        Some(secret) => pagerduty::verify_signature(&headers, &body, secret)
+            .inspect_err(|err| println!("Rejecting PagerDuty webhook for {channel}: {err}"))
+            .map_err(unauthorized)?,
+        None if params.api_key.as_deref() == Some(state.api_key.as_str()) => {}
+        None => return Err(api_key_mismatch()),
+    }
+    let data: PagerDutyData = serde_json::from_slice(&body).map_err(unprocessable)?;
+    println!("Got PagerDuty {} webhook", data.event.event_type.name());
+    deliver(
+        &state,
+        target,
+        "pagerduty",
+        None,
+        AdaptiveCardData::from(data),
+    )
+    .await
+}
+
+async fn handle_webhook_opsgenie(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    Json(data): Json<OpsgenieData>,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    println!("Got Opsgenie {:?} webhook", data.action);
+    deliver(
+        &state,
+        target,
+        "opsgenie",
+        None,
+        AdaptiveCardData::from(data),
+    )
+    .await
+}
+
+// Amazon SNS HTTPS subscription.  SNS cannot add headers, so the apiKey goes in the
+// subscription URL; on top of that every message must carry a valid SNS signature.
+async fn handle_webhook_sns(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    // SNS posts JSON with a text/plain content type
+    let message: SnsMessage = serde_json::from_slice(&body).map_err(unprocessable)?;
+    state
+        .sns_certificates
+        .verify(&message)
+        .await
+        .inspect_err(|err| println!("Rejecting SNS message for {channel}: {err}"))
+        .map_err(unauthorized)?;
+    match message.message_type {
+        MessageType::SubscriptionConfirmation => {
+            println!("Confirming SNS subscription to {}", message.topic_arn);
+            let confirmed = match &message.subscribe_url {
+                Some(url) => reqwest::get(url)
+                    .await
+                    .and_then(|res| res.error_for_status()),
+                None => {
+                    return Err(unprocessable(serde::de::Error::missing_field(
+                        "SubscribeURL",
+                    )))
+                }
+            };
+            if let Err(err) = confirmed {
+                println!("Could not confirm SNS subscription: {err}");
+                return Err(Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .body(Body::from("Subscription confirmation failed\n"))
+                    .unwrap());
+            }
+        }
+        MessageType::Notification => {
+            println!("Got SNS notification from {}", message.topic_arn);
+            return deliver(&state, target, "sns", None, AdaptiveCardData::from(message)).await;
+        }
+        MessageType::UnsubscribeConfirmation => {
+            println!("Unsubscribed from {}", message.topic_arn);
+        }
+    }
+    Ok(Response::builder()
+        .status(200)
+        .body(Body::from("Webhook processed\n"))
+        .unwrap())
+}
+
+// EventBridge API destination.  The destination's connection can send the apiKey as an
+// X-Api-Key header (API key authorization) instead of in the query string.
+async fn handle_webhook_eventbridge(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    headers: HeaderMap,
+    Json(data): Json<EventBridgeEvent>,
+) -> impl IntoResponse {
+    let key = params.api_key.or(headers
+        .get("X-Api-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from));
+    if key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    println!(
+        "Got EventBridge {} event from {}",
+        data.detail_type, data.source
+    );
+    deliver(
+        &state,
+        target,
+        "eventbridge",
+        None,
+        AdaptiveCardData::from(data),
+    )
+    .await
+}
+
+// Slack incoming webhook stand-in for tools that can only post to Slack.
+async fn handle_webhook_slack(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(channel): Path<String>,
+    Json(data): Json<SlackMessage>,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    process_slack_webhook(&state, target, data).await
+}
+
+async fn handle_webhook_generic(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path((mapping, channel)): Path<(String, String)>,
+    Json(data): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    if params.api_key.as_deref() != Some(state.api_key.as_str()) {
+        return Err(api_key_mismatch());
+    }
+    let Some(target) = state.channels.iter().find(|c| c.name == channel) else {
+        return Err(unknown_channel(&channel));
+    };
+    let Some(card_mapping) = state.mappings.get(&mapping) else {
+        println!("No generic mapping named {mapping}.");
+        return Err(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Unknown mapping\n"))
+            .unwrap());
+    };
+    println!("Got generic webhook for mapping {mapping}");
+    deliver(&state, target, "generic", None, card_mapping.apply(&data)).await
+}
+
+// The apiKey, from the query string or an X-Api-Key header.
+fn has_api_key(state: &AppState, params: &QueryParams, headers: &HeaderMap) -> bool {
+    let header = headers.get("X-Api-Key").and_then(|v| v.to_str().ok());
+    params.api_key.as_deref().or(header) == Some(state.api_key.as_str())
+}
+
+fn history_unavailable(err: rusqlite::Error) -> Response {
+    println!("Could not read the delivery history: {err}");
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::from("Unable to read the delivery history\n"))
+        .unwrap()
+}
+
+#[derive(Serialize)]
+struct DeliveriesResponse {
+    deliveries: Vec<queue::Record>,
+}
+
+// Recent deliveries, newest first, optionally only those for a `channel`, from a `source` or
+// with a `status` (pending, delivered or dead_lettered), and at most `limit` of them.
+async fn handle_deliveries(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Query(filter): Query<queue::Filter>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_key(&state, &params, &headers) {
+        return Err(api_key_mismatch());
+    }
+    let deliveries = state
+        .queue
+        .list(&filter)
+        .await
+        .map_err(history_unavailable)?;
+    Ok(Json(DeliveriesResponse { deliveries }))
+}
+
+async fn handle_delivery(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_key(&state, &params, &headers) {
+        return Err(api_key_mismatch());
+    }
+    match state.queue.get(id).await.map_err(history_unavailable)? {
+        Some(delivery) => Ok(Json(delivery)),
+        None => Err(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Unknown delivery\n"))
+            .unwrap()),
+    }
+}
+
+// Which deliveries to replay, and whether to render their messages again from what their
+// sources sent, with the current templates and channel settings, instead of resending them as
+// they were.
+#[derive(Debug, Deserialize)]
+struct ReplayRequest {
+    #[serde(flatten)]
+    selection: queue::Selection,
+    #[serde(default)]
+    rerender: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ReplayResponse {
+    replayed: Vec<Replayed>,
+    // Deliveries that couldn't be rendered again, and why
+    skipped: Vec<Skipped>,
+}
+
+#[derive(Debug, Serialize)]
+struct Replayed {
+    id: i64,
+    replay_of: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct Skipped {
+    id: i64,
+    error: String,
+}
+
+// The message for `target` rendered again from the `payload` a delivery from `source` was
+// made from.  Only the sources laid out by card templates keep their payload for this; the
+// messages of the others can only be resent as they were.
+fn rerender(
+    target: &TeamsChannelUrl,
+    source: &str,
+    payload: Option<Value>,
+) -> Result<Value, String> {
+    let payload = payload.ok_or(format!("Messages from {source} can't be rendered again"))?;
+    let message = match source {
+        "buildkite" => serde_json::from_value(payload.clone()).map(|event| {
+            serde_json::to_value(adaptive_card::buildkite_card(&event, payload))
+                .expect("messages serialize to JSON")
+        }),
+        "dms" => {
+            serde_json::from_value(payload.clone()).map(|data| dms_message(target, data, payload))
+        }
+        _ => return Err(format!("Messages from {source} can't be rendered again")),
+    };
+    let message = message.map_err(|err| format!("Unable to parse the stored payload: {err}"))?;
+    render_message(target, message).map_err(|err| err.to_string())
+}
+
+// Queues the deliveries `request` selects again, to where their channels in `channels` go now,
+// and rendered again for how they want their messages now if asked.  Deliveries for channels
+// that are gone, or whose messages can't be rendered again, are skipped.
+async fn replay(
+    queue: &queue::Queue,
+    channels: &[TeamsChannelUrl],
+    request: ReplayRequest,
+) -> rusqlite::Result<ReplayResponse> {
+    let mut response = ReplayResponse::default();
+    let mut replays = vec![];
+    for mut stored in queue.replayable(&request.selection).await? {
+        // Sent where the channel goes now, e.g. its workflow once it moved off its connector
+        let Some(target) = channels.iter().find(|c| c.name == stored.channel) else {
+            response.skipped.push(Skipped {
+                id: stored.id,
+                error: format!("No channel named {}", stored.channel),
+            });
+            continue;
+        };
+        stored.url = target.url.clone();
+        if request.rerender {
+            match rerender(target, &stored.source, stored.payload.clone()) {
+                Ok(body) => stored.body = body,
+                Err(error) => {
+                    response.skipped.push(Skipped {
+                        id: stored.id,
+                        error,
+                    });
+                    continue;
+                }
+            }
+        }
+        replays.push(stored);
+    }
+    let originals: Vec<i64> = replays.iter().map(|stored| stored.id).collect();
+    let ids = queue.replay(replays).await?;
+    response.replayed = originals
+        .into_iter()
+        .zip(ids)
+        .map(|(replay_of, id)| Replayed { id, replay_of })
+        .collect();
+    Ok(response)
+}
+
+// Queues delivered or dead-lettered deliveries again, by id, time range, channel or status,
+// e.g. everything that was dead-lettered while Teams was down.
+async fn handle_replay(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    headers: HeaderMap,
+    Json(request): Json<ReplayRequest>,
+) -> impl IntoResponse {
+    if !has_api_key(&state, &params, &headers) {
+        return Err(api_key_mismatch());
+    }
+    if request.selection.is_empty() {
+        return Err(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from(
+                "Select deliveries by ids, since, until, channel or status\n",
+            ))
+            .unwrap());
+    }
+    let response = replay(&state.queue, &state.channels, request)
+        .await
+        .map_err(|err| {
+            println!("Could not replay deliveries: {err}");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("Unable to replay the deliveries\n"))
+                .unwrap()
+        })?;
+    println!("Replaying {} deliveries", response.replayed.len());
+    Ok(Json(response))
+}
+
+async fn handle_health_check(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let mut backlog: BTreeMap<String, u64> =
+        state.channels.iter().map(|c| (c.name.clone(), 0)).collect();
+    match state.queue.backlog().await {
+        Ok(pending) => backlog.extend(pending),
+        Err(err) => println!("Could not read the delivery queue: {err}"),
+    }
     Json(HealthCheckResponse {
         status: "success".to_string(),
         url_count: state.whitelist.len().to_string(),
+        pending: backlog.values().sum(),
+        backlog: has_api_key(&state, &params, &headers).then_some(backlog),
     })
 }
 
 async fn aws_client() -> Client {
     let region_provider = RegionProviderChain::default_provider().or_else("us-west-2");
     let config = aws_config::from_env().region(region_provider).load().await;
-    println!("AWS Client connection established");
+    eprintln!("AWS Client connection established");
     Client::new(&config)
 }
 
-async fn get_teams_channels() -> Vec<TeamsChannelUrl> {
-    let client = aws_client().await;
+async fn get_teams_channels(client: &Client) -> Vec<TeamsChannelUrl> {
     let secret_key_name = "ops/production/teams-webhook-adapter/webhooks.json";
     let resp = client
         .get_secret_value()
@@ -234,44 +1199,202 @@ async fn get_teams_channels() -> Vec<TeamsChannelUrl> {
         .unwrap();
     // GPT copypasta.  TODO: Review
     // Deserialize JSON string into a HashMap
-    let map: HashMap<String, String> =
+    let map: HashMap<String, ChannelEntry> =
         serde_json::from_str(&resp.secret_string.unwrap()).expect("JSON was not well-formatted");
 
     // Convert HashMap into Vec<TeamsChannelUrl>
     let channels: Vec<TeamsChannelUrl> = map
         .into_iter()
-        .map(|(name, url)| TeamsChannelUrl { name, url })
+        .map(|(name, entry)| match entry {
+            ChannelEntry::Url(url) => TeamsChannelUrl {
+                name,
+                url,
+                ..Default::default()
+            },
+            ChannelEntry::Config(channel) => TeamsChannelUrl { name, ..channel },
+        })
         .collect();
-    println!("Teams channels processed.");
-    channels
-}
+    for channel in &channels {
+        if target::is_workflow_url(&channel.url) && !target::has_signature(&channel.url) {
+            eprintln!(
+                "Channel {}: the Workflows URL has no sig parameter, so it will refuse requests",
+                channel.name
+            );
+        }
+        if channel.format == MessageFormat::MessageCard
+            && channel.request_format() != RequestFormat::Connector
+        {
+            eprintln!(
+                "Channel {}: MessageCards are only shown by Office 365 connectors, not workflows",
+                channel.name
+            );
+        }
+    }
+    eprintln!("Teams channels processed.");
+    channels
+}
+
+// Mappings for /generic, from an optional second secret.  Mappings that fail to parse are
+// left out so the rest of the adapter still starts.
+async fn get_generic_mappings(client: &Client) -> HashMap<String, generic::Mapping> {
+    let secret_key_name = "ops/production/teams-webhook-adapter/mappings.json";
+    let secret = match client
+        .get_secret_value()
+        .secret_id(secret_key_name)
+        .send()
+        .await
+    {
+        Ok(resp) => resp.secret_string.unwrap_or_default(),
+        Err(err) => {
+            println!("No generic mappings loaded: {err}");
+            return HashMap::new();
+        }
+    };
+    let map: HashMap<String, serde_json::Value> = match serde_json::from_str(&secret) {
+        Ok(map) => map,
+        Err(err) => {
+            println!("No generic mappings loaded, {secret_key_name} is not valid JSON: {err}");
+            return HashMap::new();
+        }
+    };
+    map.into_iter()
+        .filter_map(|(name, value)| {
+            match serde_json::from_value::<generic::Mapping>(value)
+                .map_err(|err| err.to_string())
+                .and_then(|mapping| mapping.validate().map(|_| mapping))
+            {
+                Ok(mapping) => Some((name, mapping)),
+                Err(err) => {
+                    println!("Skipping generic mapping {name}: {err}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn delivery_db() -> String {
+    env::var("DELIVERY_DB").unwrap_or("deliveries.db".to_string())
+}
 
 async fn build_app_state(base_url: String) -> AppState {
-    let channels = get_teams_channels().await;
+    let client = aws_client().await;
+    let channels = get_teams_channels(&client).await;
+    let mappings = get_generic_mappings(&client).await;
     println!("Got channels: {:?}", &channels.len());
     let whitelist = teams_urls_to_array(&channels);
     AppState {
-        whitelist: whitelist,
+        whitelist,
         api_key: env::var("API_KEY").unwrap_or("<defaultapikey>".to_string()),
         base_url,
         channels,
+        sns_certificates: sns::CertificateCache::default(),
+        mappings,
+        queue: queue::Queue::open(&delivery_db()).expect("the delivery queue can be opened"),
     }
 }
 
 fn new_app(app_state: AppState) -> Router {
-    let app = Router::new()
+    Router::new()
         .route("/webhook", post(handle_webhook))
         .route("/webhookb2/*webhook_path", post(handle_webhookb2))
         .route("/api/webhook/*webhook_path", post(handle_webhook_dms))
+        .route("/github/:channel", post(handle_webhook_github))
+        .route("/gitlab/:channel", post(handle_webhook_gitlab))
+        .route("/alertmanager", post(handle_webhook_alertmanager))
+        .route("/grafana/:channel", post(handle_webhook_grafana))
+        .route("/pagerduty/:channel", post(handle_webhook_pagerduty))
+        .route("/opsgenie/:channel", post(handle_webhook_opsgenie))
+        .route("/sns/:channel", post(handle_webhook_sns))
+        .route("/eventbridge/:channel", post(handle_webhook_eventbridge))
+        .route("/slack/:channel", post(handle_webhook_slack))
+        .route("/generic/:mapping/:channel", post(handle_webhook_generic))
+        .route("/deliveries", get(handle_deliveries))
+        .route("/deliveries/:id", get(handle_delivery))
+        .route("/deliveries/replay", post(handle_replay))
         .route("/check", get(handle_health_check))
-        .with_state(app_state);
-    app
+        .with_state(app_state)
+}
+
+const REPLAY_USAGE: &str = "Usage: lessons replay [--ids <id>,...] [--since <time>] \
+    [--until <time>] [--channel <name>] [--status delivered|dead_lettered] [--rerender]";
+
+// The arguments of the replay command as the request the replay API takes.
+fn parse_replay_args(args: &[String]) -> Result<ReplayRequest, String> {
+    let mut request = serde_json::Map::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--") else {
+            return Err(format!("Unexpected argument {arg}"));
+        };
+        let value = match name {
+            "rerender" => Value::Bool(true),
+            "ids" | "since" | "until" | "channel" | "status" => {
+                let Some(value) = args.next() else {
+                    return Err(format!("{arg} needs a value"));
+                };
+                match name {
+                    "ids" => value
+                        .split(',')
+                        .map(|id| id.trim().parse::<i64>().map(Value::from))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| format!("Invalid ids {value}"))?,
+                    _ => Value::String(value.clone()),
+                }
+            }
+            _ => return Err(format!("Unknown option {arg}")),
+        };
+        request.insert(name.to_string(), value);
+    }
+    let request: ReplayRequest =
+        serde_json::from_value(Value::Object(request)).map_err(|err| err.to_string())?;
+    if request.selection.is_empty() {
+        return Err("Select deliveries by ids, since, until, channel or status".to_string());
+    }
+    Ok(request)
+}
+
+// `lessons replay ...` queues deliveries again in the DELIVERY_DB store, like the replay API,
+// for when the adapter isn't reachable.  The channels are read from Secrets Manager as at
+// startup, and a running adapter sends the replays within a minute.  Only the result, as
+// JSON, goes to stdout.
+async fn replay_command(args: &[String]) -> i32 {
+    let request = match parse_replay_args(args) {
+        Ok(request) => request,
+        Err(err) => {
+            eprintln!("{err}\n{REPLAY_USAGE}");
+            return 2;
+        }
+    };
+    let queue = match queue::Queue::open(&delivery_db()) {
+        Ok(queue) => queue,
+        Err(err) => {
+            eprintln!("Could not open the delivery queue: {err}");
+            return 1;
+        }
+    };
+    let channels = get_teams_channels(&aws_client().await).await;
+    match replay(&queue, &channels, request).await {
+        Ok(response) => {
+            println!("{}", serde_json::to_string_pretty(&response).unwrap());
+            0
+        }
+        Err(err) => {
+            eprintln!("Could not replay deliveries: {err}");
+            1
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "replay") {
+        std::process::exit(replay_command(&args[1..]).await);
+    }
     // Build list of webhook paths that we're willing to process
     let app_state = build_app_state(BASE_URL.to_string()).await;
+    tokio::spawn(app_state.queue.clone().run());
     // build our application with a single route
     let app = new_app(app_state);
     //.route("/webhook", post(handle_webhook))
@@ -301,7 +1424,7 @@ mod tests {
         let response = res.into_response();
         // Aggregate the body data into a single chunk, handling errors
         let body = response.into_body();
-        let bytes = body::to_bytes(body, 100).await.unwrap();
+        let bytes = body::to_bytes(body, usize::MAX).await.unwrap();
 
         // Parse the bytes as JSON
         let json: Value = serde_json::from_slice(&bytes)?;
@@ -317,13 +1440,20 @@ mod tests {
     }
 
     use super::*;
+    use lessons::{builder, card};
 
     #[tokio::test]
     async fn test_health_check() {
         let app_state = build_app_state("127.0.0.1".to_string()).await;
-        let response_string =
-            into_response_json_status(handle_health_check(axum::extract::State(app_state)).await)
-                .await;
+        let response_string = into_response_json_status(
+            handle_health_check(
+                axum::extract::State(app_state),
+                Query(QueryParams::default()),
+                HeaderMap::new(),
+            )
+            .await,
+        )
+        .await;
         assert_eq!(response_string.unwrap(), "success");
     }
 
@@ -333,6 +1463,1165 @@ mod tests {
         //assert_eq!(build_dms_post_data(data_object: DmsData) -> PostData )
     }
 
+    fn dms_json() -> Value {
+        serde_json::json!({
+            "type": "snitch.missing",
+            "timestamp": "2024-04-30T05:25:37.166Z",
+            "data": {
+                "snitch": {
+                    "token": "c2354d53d2",
+                    "name": "Critical System Reports",
+                    "notes": "Useful notes for dealing with the situation",
+                    "tags": ["critical", "reports"],
+                    "status": "missing",
+                    "previous_status": "healthy"
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_dms_card() {
+        let data: DmsData = serde_json::from_value(dms_json()).unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "Critical System Reports is missing"
+        );
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][2]["type"], "FactSet");
+        assert_eq!(content["body"][2]["facts"][0]["value"], "critical, reports");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://deadmanssnitch.com/snitches/c2354d53d2"
+        );
+    }
+
+    #[test]
+    fn test_dms_unknown_type_and_status() {
+        let mut json = dms_json();
+        json["type"] = "snitch.exploded".into();
+        json["data"]["snitch"]["status"] = "on_fire".into();
+        json["data"]["snitch"]["previous_status"] = Value::Null;
+        json["data"]["snitch"]["notes"] = Value::Null;
+        let data: DmsData = serde_json::from_value(json).unwrap();
+        assert!(
+            matches!(&data.snitch_type, snitch::SnitchType::Other(t) if t == "snitch.exploded")
+        );
+
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let body = &card["attachments"][0]["content"]["body"];
+        assert_eq!(
+            body[0]["text"],
+            "Critical System Reports sent snitch.exploded"
+        );
+        assert_eq!(body[1]["text"], "Status is **on_fire**");
+
+        let mut json = dms_json();
+        json["type"] = "snitch.errored".into();
+        let data: DmsData = serde_json::from_value(json).unwrap();
+        assert!(build_dms_post_data(data).text.contains("has errored"));
+    }
+
+    fn buildkite_build_json() -> Value {
+        serde_json::json!({
+            "event": "build.finished",
+            "build": {
+                "number": 42,
+                "web_url": "https://buildkite.com/acme/app/builds/42",
+                "state": "failed",
+                "commit": "abc123",
+                "creator": {"name": "Ada", "avatar_url": "https://example.com/ada.png"},
+                "created_at": "2024-05-01 10:00:00 UTC"
+            },
+            "pipeline": {
+                "name": "app",
+                "web_url": "https://buildkite.com/acme/app",
+                "repository": "git@github.com:acme/app.git"
+            },
+            "sender": {"name": "Ada"}
+        })
+    }
+
+    #[test]
+    fn test_buildkite_build_card() {
+        let data = parse_webhook_data(&HeaderMap::new(), buildkite_build_json()).unwrap();
+        let WebhookData::TypeA(event, _) = data else {
+            panic!("expected a Buildkite event");
+        };
+        let card = serde_json::to_value(AdaptiveCardData::from(*event)).unwrap();
+        let body = &card["attachments"][0]["content"]["body"];
+        assert_eq!(body[0]["text"], "app #42 Failed");
+        assert_eq!(body[0]["color"], "Attention");
+        assert_eq!(
+            card["attachments"][0]["content"]["actions"][0]["url"],
+            "https://buildkite.com/acme/app/builds/42"
+        );
+
+        // States read as words, and ones Buildkite adds later as it names them
+        for (state, title) in [("not_run", "app #42 Not run"), ("paused", "app #42 paused")] {
+            let mut json = buildkite_build_json();
+            json["build"]["state"] = state.into();
+            let data = parse_webhook_data(&HeaderMap::new(), json).unwrap();
+            let WebhookData::TypeA(event, _) = data else {
+                panic!("expected a Buildkite event");
+            };
+            let card = serde_json::to_value(AdaptiveCardData::from(*event)).unwrap();
+            assert_eq!(card["attachments"][0]["content"]["body"][0]["text"], title);
+        }
+    }
+
+    #[test]
+    fn test_buildkite_event_from_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Buildkite-Event", "agent.lost".parse().unwrap());
+        let data = parse_webhook_data(
+            &headers,
+            serde_json::json!({
+                "agent": {"id": "a1", "name": "builder-1", "connection_state": "lost"},
+                "sender": {"name": "Buildkite"}
+            }),
+        )
+        .unwrap();
+        match data {
+            WebhookData::TypeA(event, _) => assert_eq!(event.name(), "agent.lost"),
+            _ => panic!("expected a Buildkite event"),
+        }
+
+        // An `event` field alone doesn't make a payload Buildkite's
+        let data = parse_webhook_data(
+            &HeaderMap::new(),
+            serde_json::json!({"text": "Deployed app", "event": "deploy"}),
+        )
+        .unwrap();
+        match data {
+            WebhookData::TypeB(post_data) => assert_eq!(post_data.text, "Deployed app"),
+            _ => panic!("expected a plain text message"),
+        }
+    }
+
+    #[test]
+    fn test_buildkite_event_names() {
+        // Every name in EVENTS parses as the event of that name...
+        let mut payload = buildkite_build_json();
+        payload["job"] = serde_json::json!({"id": "j1", "type": "script", "state": "passed"});
+        payload["agent"] =
+            serde_json::json!({"id": "a1", "name": "builder-1", "connection_state": "lost"});
+        payload["blocked_ip"] = "10.0.0.1".into();
+        payload["cluster_token"] = serde_json::json!({"uuid": "t1"});
+        payload["service"] = serde_json::json!({"id": "svc", "provider": "webhook"});
+        payload["organization"] = serde_json::json!({"name": "Acme"});
+        for name in buildkite::EVENTS {
+            payload["event"] = name.into();
+            let event: BuildkiteEvent = serde_json::from_value(payload.clone()).unwrap();
+            assert_eq!(event.name(), name);
+            assert!(BuildkiteEvent::is_known(name));
+        }
+        // ...and there are no others, going by the names serde expects for one it doesn't know
+        payload["event"] = "deploy".into();
+        let err = serde_json::from_value::<BuildkiteEvent>(payload)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err.matches('`').count(),
+            2 * (buildkite::EVENTS.len() + 1),
+            "{err}"
+        );
+        assert!(!BuildkiteEvent::is_known("deploy"));
+    }
+
+    fn buildkite_signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{timestamp}.").as_bytes());
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+        format!("timestamp={timestamp},signature={signature}")
+    }
+
+    #[test]
+    fn test_buildkite_signature_verification() {
+        let body = buildkite_build_json().to_string();
+        let now = 1_700_000_000;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Buildkite-Signature",
+            buildkite_signature("s3cret", now - 10, body.as_bytes())
+                .parse()
+                .unwrap(),
+        );
+        let verify = |headers: &HeaderMap, body: &str, now| {
+            buildkite::verify_request(headers, body.as_bytes(), None, Some("s3cret"), now)
+        };
+
+        assert_eq!(verify(&headers, &body, now), Ok(()));
+        assert_eq!(
+            verify(&headers, &body.replace("abc123", "def456"), now),
+            Err(signature::SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify(&headers, &body, now + signature::REPLAY_WINDOW_SECS),
+            Err(signature::SignatureError::Expired)
+        );
+        assert_eq!(
+            verify(&HeaderMap::new(), &body, now),
+            Err(signature::SignatureError::MissingHeader(
+                "X-Buildkite-Signature"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_github_workflow_run() {
+        let body = serde_json::json!({
+            "action": "completed",
+            "workflow_run": {
+                "name": "CI",
+                "run_number": 7,
+                "event": "push",
+                "status": "completed",
+                "conclusion": "failure",
+                "html_url": "https://github.com/acme/app/actions/runs/1",
+                "head_branch": "main",
+                "head_sha": "abc123",
+                "actor": {"login": "ada", "avatar_url": "https://example.com/ada.png"}
+            },
+            "repository": {"full_name": "acme/app", "html_url": "https://github.com/acme/app"},
+            "sender": {"login": "ada"}
+        })
+        .to_string();
+
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"gh-secret").unwrap();
+        mac.update(body.as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Hub-Signature-256",
+            format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            github::verify_signature(&headers, body.as_bytes(), "gh-secret"),
+            Ok(())
+        );
+        assert_eq!(
+            github::verify_signature(&headers, body.as_bytes(), "wrong"),
+            Err(signature::SignatureError::Mismatch)
+        );
+
+        let event = GithubEvent::parse("workflow_run", body.as_bytes()).unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(event)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "CI #7 Failure");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert!(GithubEvent::parse("star", body.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_gitlab_pipeline_card() {
+        let data: GitlabEvent = serde_json::from_value(serde_json::json!({
+            "object_kind": "pipeline",
+            "object_attributes": {
+                "id": 31,
+                "ref": "main",
+                "sha": "bcbb5ec396a2c0f828686f14fac9b80b780504f2",
+                "status": "failed",
+                "source": "push"
+            },
+            "user": {"name": "Administrator", "username": "root"},
+            "project": {
+                "name": "Gitlab Test",
+                "path_with_namespace": "gitlab-org/gitlab-test",
+                "web_url": "https://gitlab.example.com/gitlab-org/gitlab-test"
+            },
+            "builds": [
+                {"id": 380, "stage": "deploy", "name": "production", "status": "skipped"},
+                {"id": 377, "stage": "test", "name": "test-image", "status": "failed",
+                 "failure_reason": "script_failure"}
+            ]
+        }))
+        .unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "gitlab-org/gitlab-test pipeline #31 Failed"
+        );
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][5]["facts"][0]["title"], "test");
+        assert_eq!(
+            content["body"][5]["facts"][0]["value"],
+            "test-image (script_failure)"
+        );
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://gitlab.example.com/gitlab-org/gitlab-test/-/pipelines/31"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Gitlab-Token", "tok".parse().unwrap());
+        assert_eq!(gitlab::verify_token(&headers, "tok"), Ok(()));
+        assert_eq!(
+            gitlab::verify_token(&HeaderMap::new(), "tok"),
+            Err(signature::SignatureError::MissingHeader("X-Gitlab-Token"))
+        );
+    }
+
+    #[test]
+    fn test_alertmanager_card() {
+        let data: AlertmanagerData = serde_json::from_value(serde_json::json!({
+            "version": "4",
+            "groupKey": "{}:{alertname=\"HighLatency\"}",
+            "truncatedAlerts": 0,
+            "status": "firing",
+            "receiver": "teams",
+            "groupLabels": {"alertname": "HighLatency", "teams_channel": "ops"},
+            "commonLabels": {"alertname": "HighLatency", "severity": "page"},
+            "commonAnnotations": {"summary": "p99 latency above 2s"},
+            "externalURL": "http://alertmanager:9093",
+            "alerts": [{
+                "status": "firing",
+                "labels": {"alertname": "HighLatency", "instance": "web-1"},
+                "annotations": {},
+                "startsAt": "2024-05-01T10:00:00Z",
+                "endsAt": "0001-01-01T00:00:00Z",
+                "generatorURL": "http://prometheus:9090/graph?g0.expr=latency",
+                "fingerprint": "abc"
+            }]
+        }))
+        .unwrap();
+        assert_eq!(data.channel_label().map(String::as_str), Some("ops"));
+
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "[FIRING:1] HighLatency");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][2]["facts"][1]["title"], "severity");
+        assert_eq!(
+            content["actions"][1]["url"],
+            "http://alertmanager:9093/#/silences/new\
+             ?filter=%7Balertname%3D%22HighLatency%22%2Cseverity%3D%22page%22%7D"
+        );
+    }
+
+    #[test]
+    fn test_grafana_card() {
+        let data: GrafanaData = serde_json::from_value(serde_json::json!({
+            "title": "[Alerting] Panel Title alert",
+            "ruleId": 1,
+            "ruleName": "Panel Title alert",
+            "ruleUrl": "http://grafana/d/abc/dash?panelId=2",
+            "state": "alerting",
+            "imageUrl": "https://grafana.com/static/assets/img/blog/mixed_styles.png",
+            "message": "Notification Message",
+            "evalMatches": [{"value": 100, "metric": "High value", "tags": null}]
+        }))
+        .unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "[Alerting] Panel Title alert");
+        assert_eq!(content["body"][2]["facts"][0]["title"], "High value");
+        assert_eq!(content["body"][2]["facts"][0]["value"], "100");
+        assert_eq!(content["body"][3]["type"], "Image");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "http://grafana/d/abc/dash?panelId=2"
+        );
+    }
+
+    #[test]
+    fn test_sentry_issue_card() {
+        let body = serde_json::json!({
+            "action": "created",
+            "installation": {"uuid": "7a485448-a9e2-4c85-8a3c-4f44175783c9"},
+            "data": {
+                "issue": {
+                    "id": "1170820242",
+                    "shortId": "APP-1",
+                    "title": "TypeError: Cannot read property 'id' of undefined",
+                    "culprit": "app/components/user.js in render",
+                    "level": "error",
+                    "status": "unresolved",
+                    "project": {"name": "app", "slug": "app"},
+                    "count": "12",
+                    "userCount": 3,
+                    "firstSeen": "2024-05-01T10:00:00Z",
+                    "lastSeen": "2024-05-01T11:00:00Z",
+                    "web_url": "https://sentry.io/organizations/acme/issues/1170820242/"
+                }
+            },
+            "actor": {"type": "application", "id": "sentry", "name": "Sentry"}
+        })
+        .to_string();
+
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"client-secret").unwrap();
+        mac.update(body.as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert("Sentry-Hook-Resource", "issue".parse().unwrap());
+        headers.insert(
+            "Sentry-Hook-Signature",
+            hex::encode(mac.finalize().into_bytes()).parse().unwrap(),
+        );
+        let channel = TeamsChannelUrl {
+            sentry_secret: Some("client-secret".to_string()),
+            ..Default::default()
+        };
+
+        let data = parse_webhook_data(&headers, serde_json::from_str(&body).unwrap()).unwrap();
+        assert_eq!(
+            verify_source_request(&headers, body.as_bytes(), &data, Some(&channel)),
+            Ok(())
+        );
+        assert_eq!(
+            verify_source_request(&headers, b"{}", &data, Some(&channel)),
+            Err(signature::SignatureError::Mismatch)
+        );
+        // Unsigned text can't get past the channel's secret, but is fine for other channels
+        let text =
+            parse_webhook_data(&HeaderMap::new(), serde_json::json!({"text": "hi"})).unwrap();
+        assert_eq!(
+            verify_source_request(&HeaderMap::new(), b"", &text, Some(&channel)),
+            Err(signature::SignatureError::Unsigned)
+        );
+        assert_eq!(
+            verify_source_request(
+                &HeaderMap::new(),
+                b"",
+                &text,
+                Some(&TeamsChannelUrl::default())
+            ),
+            Ok(())
+        );
+        let WebhookData::Sentry(event) = data else {
+            panic!("expected a Sentry event");
+        };
+        let card = serde_json::to_value(AdaptiveCardData::from(*event)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "APP-1 created TypeError: Cannot read property 'id' of undefined"
+        );
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "12");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://sentry.io/organizations/acme/issues/1170820242/"
+        );
+
+        // A Sentry payload without its header is not taken for a text message
+        assert!(
+            parse_webhook_data(&HeaderMap::new(), serde_json::from_str(&body).unwrap()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_pagerduty_incident_card() {
+        let body = serde_json::json!({
+            "event": {
+                "id": "01BZD2BKJ0GPYJ3Q8DKD8S8A0Y",
+                "event_type": "incident.acknowledged",
+                "resource_type": "incident",
+                "occurred_at": "2024-05-01T10:00:00.000Z",
+                "agent": {"id": "PLH1HKV", "summary": "Tenex Engineer", "type": "user_reference"},
+                "data": {
+                    "id": "PGR0VU2",
+                    "type": "incident",
+                    "html_url": "https://acme.pagerduty.com/incidents/PGR0VU2",
+                    "number": 2,
+                    "status": "acknowledged",
+                    "title": "A little bump in the road",
+                    "urgency": "high",
+                    "service": {
+                        "id": "PF9KMXH",
+                        "summary": "API Service",
+                        "html_url": "https://acme.pagerduty.com/services/PF9KMXH"
+                    },
+                    "assignees": [{"id": "PTUXL6G", "summary": "User 123"}],
+                    "priority": {"id": "PSO75BM", "summary": "P1"}
+                }
+            }
+        })
+        .to_string();
+
+        use hmac::{Hmac, Mac};
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"pd-secret").unwrap();
+        mac.update(body.as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-PagerDuty-Signature",
+            format!(
+                "v1=deadbeef,v1={}",
+                hex::encode(mac.finalize().into_bytes())
+            )
+            .parse()
+            .unwrap(),
+        );
+        assert_eq!(
+            pagerduty::verify_signature(&headers, body.as_bytes(), "pd-secret"),
+            Ok(())
+        );
+        assert_eq!(
+            pagerduty::verify_signature(&headers, body.as_bytes(), "rotated"),
+            Err(signature::SignatureError::Mismatch)
+        );
+
+        let data: PagerDutyData = serde_json::from_str(&body).unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "[#2] A little bump in the road acknowledged"
+        );
+        assert_eq!(content["body"][0]["color"], "Warning");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "high");
+        assert_eq!(content["body"][2]["facts"][3]["value"], "User 123");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://acme.pagerduty.com/incidents/PGR0VU2"
+        );
+    }
+
+    #[test]
+    fn test_opsgenie_card() {
+        let data: OpsgenieData = serde_json::from_value(serde_json::json!({
+            "action": "Create",
+            "alert": {
+                "alertId": "70413a06-38d6-4c85-92b8-5ebc900d42e2",
+                "message": "Disk almost full on db-1",
+                "tags": ["db"],
+                "tinyId": "1791",
+                "entity": "db-1",
+                "username": "Alert API",
+                "priority": "P2",
+                "responders": [{"id": "c4d6", "type": "team", "name": "DBA"}]
+            },
+            "source": {"name": "", "type": "API"},
+            "integrationName": "Teams"
+        }))
+        .unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(data)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "[#1791] Disk almost full on db-1 created"
+        );
+        assert_eq!(content["body"][2]["facts"][0]["value"], "P2");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "DBA");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://app.opsgenie.com/alert/detail/70413a06-38d6-4c85-92b8-5ebc900d42e2/details"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sns_cloudwatch_alarm() {
+        let certificates = sns::CertificateCache::default();
+        let mut message: SnsMessage =
+            serde_json::from_str(include_str!("../tests/fixtures/sns-cloudwatch-alarm.json"))
+                .unwrap();
+        certificates
+            .insert_pem(
+                &message.signing_cert_url,
+                include_str!("../tests/fixtures/sns-signing-cert.pem"),
+            )
+            .unwrap();
+        // A minute after it was sent
+        let now = 1714557961;
+        assert_eq!(certificates.verify_at(&message, now).await, Ok(()));
+        // Captured messages can't be replayed later on
+        assert_eq!(
+            certificates.verify_at(&message, now + 2 * 60 * 60).await,
+            Err(signature::SignatureError::Expired)
+        );
+        assert_eq!(
+            certificates.verify(&message).await,
+            Err(signature::SignatureError::Expired)
+        );
+
+        let card = serde_json::to_value(AdaptiveCardData::from(message)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "ALARM: api-high-cpu");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        let facts = &content["body"][3]["facts"];
+        assert_eq!(facts[0]["value"], "OK → ALARM");
+        assert_eq!(
+            facts[1]["value"],
+            "AWS/EC2 CPUUtilization (InstanceId=i-0abc123)"
+        );
+        assert_eq!(
+            facts[2]["value"],
+            "AVERAGE GreaterThanThreshold 80 for 1 x 300s"
+        );
+        assert_eq!(facts[3]["value"], "US East (N. Virginia)");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://console.aws.amazon.com/cloudwatch/home\
+             ?region=us-east-1#alarmsV2:alarm/api-high-cpu"
+        );
+
+        message = serde_json::from_str(include_str!("../tests/fixtures/sns-cloudwatch-alarm.json"))
+            .unwrap();
+        message.subject = Some("OK: all good".to_string());
+        assert_eq!(
+            certificates.verify_at(&message, now).await,
+            Err(signature::SignatureError::Mismatch)
+        );
+        for url in [
+            "https://example.com/SimpleNotificationService.pem",
+            "https://sns.s3.amazonaws.com/SimpleNotificationService.pem",
+            "https://sns.us-east-1.amazonaws.com.example.com/SimpleNotificationService.pem",
+        ] {
+            message.signing_cert_url = url.to_string();
+            assert!(matches!(
+                certificates.verify_at(&message, now).await,
+                Err(signature::SignatureError::Certificate(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_eventbridge_cards() {
+        let ecs: EventBridgeEvent = serde_json::from_value(serde_json::json!({
+            "version": "0",
+            "id": "3317b2af-7005-947d-b652-f55e762e571a",
+            "detail-type": "ECS Task State Change",
+            "source": "aws.ecs",
+            "account": "111122223333",
+            "time": "2024-05-01T10:00:00Z",
+            "region": "us-west-2",
+            "resources": ["arn:aws:ecs:us-west-2:111122223333:task/default/a1b2c3"],
+            "detail": {
+                "clusterArn": "arn:aws:ecs:us-west-2:111122223333:cluster/default",
+                "taskArn": "arn:aws:ecs:us-west-2:111122223333:task/default/a1b2c3",
+                "taskDefinitionArn": "arn:aws:ecs:us-west-2:111122223333:task-definition/web:12",
+                "group": "service:web",
+                "lastStatus": "STOPPED",
+                "desiredStatus": "STOPPED",
+                "stopCode": "EssentialContainerExited",
+                "stoppedReason": "Essential container in task exited",
+                "containers": [{"name": "app", "exitCode": 137, "lastStatus": "STOPPED"}]
+            }
+        }))
+        .unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(ecs)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "ECS task service:web stopped");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "web:12");
+        assert_eq!(content["body"][2]["facts"][3]["value"], "exit code 137");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://us-west-2.console.aws.amazon.com/ecs/v2/clusters/default/tasks/a1b2c3\
+             ?region=us-west-2"
+        );
+
+        let unknown: EventBridgeEvent = serde_json::from_value(serde_json::json!({
+            "detail-type": "Scheduled Backup Completed",
+            "source": "com.acme.backups",
+            "detail": {"database": "orders", "sizeBytes": 1024}
+        }))
+        .unwrap();
+        let card = serde_json::to_value(AdaptiveCardData::from(unknown)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "Scheduled Backup Completed");
+        assert_eq!(content["body"][2]["facts"][0]["title"], "database");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "1024");
+    }
+
+    #[test]
+    fn test_slack_message_card() {
+        let data = parse_webhook_data(
+            &HeaderMap::new(),
+            serde_json::from_str(include_str!("../tests/fixtures/slack-blocks.json")).unwrap(),
+        )
+        .unwrap();
+        let WebhookData::Slack(message) = data else {
+            panic!("expected a Slack message");
+        };
+        let card = serde_json::to_value(AdaptiveCardData::from(message)).unwrap();
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "Deploy finished");
+        assert_eq!(
+            content["body"][1]["text"],
+            "**api** deployed by @ada to [production](https://prod.example.com) & staging"
+        );
+        assert_eq!(content["body"][2]["text"], "took 42s");
+        assert_eq!(
+            content["body"][3]["text"],
+            "[Errors](https://errors.example.com)"
+        );
+        assert_eq!(content["body"][3]["color"], "Attention");
+        assert_eq!(content["body"][4]["facts"][0]["value"], "**3**");
+        assert_eq!(content["actions"][0]["title"], "View logs");
+    }
+
+    #[test]
+    fn test_generic_mapping() {
+        let payload = serde_json::json!({
+            "incident": {
+                "name": "Elevated API errors",
+                "status": "investigating",
+                "impact": "major",
+                "shortlink": "https://stspg.io/abc",
+                "components": [{"name": "API"}, {"name": "Web"}],
+                "updates": [{"body": "We are looking into it."}]
+            }
+        });
+        let mapping: generic::Mapping = serde_json::from_str(include_str!(
+            "../tests/fixtures/generic-mapping-fields.json"
+        ))
+        .unwrap();
+        assert_eq!(mapping.validate(), Ok(()));
+        let content = &mapping.apply(&payload)["attachments"][0]["content"];
+        assert_eq!(
+            content["body"][0]["text"],
+            "Elevated API errors (investigating)"
+        );
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][1]["text"], "We are looking into it.");
+        assert_eq!(content["body"][2]["facts"].as_array().unwrap().len(), 1);
+        assert_eq!(content["actions"][0]["url"], "https://stspg.io/abc");
+
+        let mapping: generic::Mapping = serde_json::from_str(include_str!(
+            "../tests/fixtures/generic-mapping-template.json"
+        ))
+        .unwrap();
+        let content = &mapping.apply(&payload)["attachments"][0]["content"];
+        assert_eq!(content["type"], "AdaptiveCard");
+        assert_eq!(content["body"][0]["text"], "Incident: Elevated API errors");
+        assert_eq!(
+            content["body"][1]["facts"][0]["value"],
+            serde_json::json!(["API", "Web"])
+        );
+        assert_eq!(content["body"][2]["text"], "Affects API, Web");
+        assert_eq!(content["body"].as_array().unwrap().len(), 3);
+        assert_eq!(content["msteams"]["entities"], Value::Null);
+
+        for broken in [
+            serde_json::json!({"title": "$.incident[name"}),
+            serde_json::json!({"title": "${nope(incident.name)}"}),
+            serde_json::json!({"template": {"body": [{"type": "TextBlock", "text": "${$.x}"}]}}),
+        ] {
+            let broken: generic::Mapping = serde_json::from_value(broken).unwrap();
+            assert!(broken.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_card_template() {
+        let data = serde_json::json!({
+            "title": "Deploy",
+            "finished": "2024-05-01T10:04:05Z",
+            "extra": "{\"region\": \"eu-west-1\"}",
+            "steps": [{"name": "build", "ok": true}, {"name": "test", "ok": false}],
+            "owner": null
+        });
+        let card = template::expand(
+            &serde_json::from_str::<Value>(include_str!("../tests/fixtures/card-template.json"))
+                .unwrap(),
+            &data,
+        )
+        .unwrap();
+        assert_eq!(card["body"][0]["text"], "DEPLOY at May 1, 10:04");
+        assert_eq!(card["body"][1]["text"], "1. build passed (Deploy)");
+        assert_eq!(card["body"][2]["text"], "2. test failed (Deploy)");
+        assert_eq!(card["body"][3]["facts"][0]["value"], "eu-west-1");
+        assert_eq!(card["count"], 2);
+        assert!(template::expand(&serde_json::json!("${nope(title)}"), &data).is_err());
+
+        let ping = || -> BuildkiteEvent {
+            serde_json::from_value(serde_json::json!({
+                "event": "ping",
+                "service": {"id": "svc", "provider": "webhook"},
+                "organization": {"name": "Acme"},
+                "sender": {"name": "Jo"}
+            }))
+            .unwrap()
+        };
+        let message = serde_json::to_value(AdaptiveCardData::from(ping())).unwrap();
+        let content = &message["attachments"][0]["content"];
+        assert_eq!(content["version"], "1.5");
+        assert_eq!(
+            content["body"][0]["text"],
+            "Buildkite webhook ping from Acme"
+        );
+        assert_eq!(content["actions"], serde_json::json!([]));
+
+        let dir = std::env::temp_dir().join(format!("card-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("buildkite-ping.json"),
+            r#"{"body": [{"type": "TextBlock", "text": "Ping from ${organization.name}"}]}"#,
+        )
+        .unwrap();
+        // The directory is passed in rather than set in CARD_TEMPLATE_DIR, which other tests
+        // running at the same time would see
+        let data = serde_json::json!({"organization": {"name": "Acme"}});
+        let message = serde_json::to_value(adaptive_card::from_template_in(
+            Some(&dir),
+            "buildkite-ping",
+            &data,
+        ))
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let content = &message["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "Ping from Acme");
+    }
+
+    #[test]
+    fn test_card_model_round_trip() {
+        let json = serde_json::from_str::<Value>(include_str!("../tests/fixtures/card-model.json"))
+            .unwrap();
+        let card: card::AdaptiveCard = serde_json::from_value(json.clone()).unwrap();
+        let body = card.body.as_ref().unwrap();
+        assert!(matches!(&body[1], card::Element::Container(c) if c.items.len() == 2));
+        assert!(matches!(&body[11], card::Element::Unknown(_)));
+        assert!(matches!(
+            &card.actions.as_ref().unwrap()[2],
+            card::Action::ShowCard(_)
+        ));
+        assert_eq!(serde_json::to_value(&card).unwrap(), json);
+
+        let text: card::Element = serde_json::from_value(serde_json::json!({
+            "type": "TextBlock",
+            "text": "x",
+            "color": "attention",
+            "size": "medium"
+        }))
+        .unwrap();
+        let card::Element::TextBlock(text) = text else {
+            panic!("expected a TextBlock");
+        };
+        assert_eq!(text.color, Some(card::Color::Attention));
+        assert_eq!(serde_json::to_value(&text).unwrap()["size"], "Medium");
+    }
+
+    #[test]
+    fn test_card_builder() {
+        let message = AdaptiveCardData::from(
+            builder::Card::new()
+                .title("app #42 failed", card::Color::Attention)
+                .byline("octocat", None, "Triggered by push")
+                .facts(Vec::<(&str, &str)>::new())
+                .facts([("Branch", "main")])
+                .column_set([
+                    builder::column("2", vec![]),
+                    builder::column("auto", vec![]),
+                ])
+                .open_url("View build", "https://buildkite.com/acme/app/builds/42"),
+        );
+        let json = serde_json::to_value(&message).unwrap();
+        let content = &json["attachments"][0]["content"];
+        assert_eq!(content["type"], "AdaptiveCard");
+        assert_eq!(content["version"], "1.5");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][0]["weight"], "Bolder");
+        // No avatar, so just the name column; empty fact lists are left out
+        assert_eq!(content["body"][1]["columns"].as_array().unwrap().len(), 1);
+        assert_eq!(content["body"][2]["facts"][0]["value"], "main");
+        assert_eq!(content["body"][3]["columns"][0]["width"], 2);
+        assert_eq!(content["body"][3]["columns"][1]["width"], "auto");
+        assert_eq!(content["actions"][0]["type"], "Action.OpenUrl");
+        assert_eq!(content["actions"][0]["title"], "View build");
+    }
+
+    #[test]
+    fn test_workflow_targets() {
+        let workflow = "https://prod-12.westus.logic.azure.com:443/workflows/abc/triggers/manual/\
+            paths/invoke?api-version=2016-06-01&sp=%2Ftriggers%2Fmanual%2Frun&sv=1.0&sig=s3cret";
+        let connector = "https://acme.webhook.office.com/webhookb2/aaa@bbb/IncomingWebhook/ccc/ddd";
+        let power_automate = "https://default1234.56.environment.api.powerplatform.com/\
+            powerautomate/automations/direct/workflows/abc/triggers/manual/paths/invoke?sig=x";
+        assert_eq!(RequestFormat::for_url(workflow), RequestFormat::Workflow);
+        assert_eq!(
+            RequestFormat::for_url(power_automate),
+            RequestFormat::Workflow
+        );
+        assert_eq!(RequestFormat::for_url(connector), RequestFormat::Connector);
+        assert!(target::has_signature(workflow));
+        assert!(!target::has_signature(
+            workflow.split_once("&sig").unwrap().0
+        ));
+
+        let map: HashMap<String, ChannelEntry> = serde_json::from_value(serde_json::json!({
+            "ops": connector,
+            "moved": {
+                "url": workflow,
+                "connector_url":
+                    "https://acme.webhook.office.com/webhookb2/old@path/IncomingWebhook/1/2"
+            },
+            "flow": {"url": workflow, "request_format": "card"}
+        }))
+        .unwrap();
+        let channels: Vec<TeamsChannelUrl> = map
+            .into_iter()
+            .map(|(name, entry)| match entry {
+                ChannelEntry::Url(url) => TeamsChannelUrl {
+                    name,
+                    url,
+                    ..Default::default()
+                },
+                ChannelEntry::Config(channel) => TeamsChannelUrl { name, ..channel },
+            })
+            .collect();
+        let mut whitelist = teams_urls_to_array(&channels);
+        whitelist.sort();
+        assert_eq!(
+            whitelist,
+            [
+                "aaa@bbb/IncomingWebhook/ccc/ddd",
+                "old@path/IncomingWebhook/1/2"
+            ]
+        );
+        let state = AppState {
+            whitelist,
+            channels,
+            base_url: "https://acme.webhook.office.com/webhookb2/".to_string(),
+            api_key: String::new(),
+            sns_certificates: sns::CertificateCache::default(),
+            mappings: HashMap::new(),
+            queue: queue::Queue::open(":memory:").unwrap(),
+        };
+        let moved = webhookb2_target(&state, "old@path/IncomingWebhook/1/2");
+        assert_eq!(moved.name, "moved");
+        assert_eq!(moved.url, workflow);
+        assert_eq!(moved.request_format(), RequestFormat::Workflow);
+        let ops = webhookb2_target(&state, "aaa@bbb/IncomingWebhook/ccc/ddd");
+        assert_eq!(ops.url, connector);
+        assert_eq!(ops.request_format(), RequestFormat::Connector);
+        let flow = state.channels.iter().find(|c| c.name == "flow").unwrap();
+        assert_eq!(flow.request_format(), RequestFormat::Card);
+
+        // Plain text goes to Workflows as a card, and a card on its own when asked for
+        let text = serde_json::json!({"text": "hello"});
+        assert_eq!(
+            target::request_body(RequestFormat::Connector, text.clone()),
+            text
+        );
+        let message = target::request_body(RequestFormat::Workflow, text.clone());
+        assert_eq!(message["type"], "message");
+        assert_eq!(
+            message["attachments"][0]["content"]["body"][0]["text"],
+            "hello"
+        );
+        let card = target::request_body(RequestFormat::Card, text);
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["text"], "hello");
+    }
+
+    #[test]
+    fn test_message_card() {
+        let dms: DmsData = serde_json::from_value(dms_json()).unwrap();
+        let message = serde_json::to_value(AdaptiveCardData::from(dms)).unwrap();
+        let card = serde_json::to_value(MessageCard::from_message(&message).unwrap()).unwrap();
+        assert_eq!(card["@type"], "MessageCard");
+        assert_eq!(card["title"], "Critical System Reports is missing");
+        assert_eq!(card["summary"], "Critical System Reports is missing");
+        assert_eq!(card["themeColor"], "CF222E");
+        let facts = card["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|section| section.get("facts").is_some())
+            .unwrap();
+        assert_eq!(facts["facts"][0]["value"], "critical, reports");
+        assert_eq!(card["potentialAction"][0]["@type"], "OpenUri");
+        assert_eq!(
+            card["potentialAction"][0]["targets"][0]["uri"],
+            "https://deadmanssnitch.com/snitches/c2354d53d2"
+        );
+
+        let WebhookData::TypeA(build, _) =
+            parse_webhook_data(&HeaderMap::new(), buildkite_build_json()).unwrap()
+        else {
+            panic!("expected a Buildkite event");
+        };
+        let message = serde_json::to_value(AdaptiveCardData::from(*build)).unwrap();
+        let card = MessageCard::from_message(&message).unwrap();
+        assert!(card.title.is_some());
+        assert!(!card.potential_action.is_empty());
+
+        let card: lessons::card::AdaptiveCard = builder::Card::new()
+            .title("app #42 failed", card::Color::Attention)
+            .byline("Ada", Some("https://example.com/ada.png"), "pushed to main")
+            .text("Tests failed")
+            .facts([("Branch", "main")])
+            .image("https://example.com/graph.png", "Graph")
+            .build();
+        let card = MessageCard::from(&card);
+        assert_eq!(card.title.as_deref(), Some("app #42 failed"));
+        // The byline and what follows it share a section, as activity and body
+        assert_eq!(card.sections.len(), 1);
+        let section = &card.sections[0];
+        assert_eq!(section.activity_title.as_deref(), Some("Ada"));
+        assert_eq!(section.activity_subtitle.as_deref(), Some("pushed to main"));
+        assert_eq!(
+            section.activity_image.as_deref(),
+            Some("https://example.com/ada.png")
+        );
+        assert_eq!(section.text.as_deref(), Some("Tests failed"));
+        assert_eq!(section.facts[0].name, "Branch");
+        assert_eq!(section.images[0].image, "https://example.com/graph.png");
+
+        // Plain text messages become a card with only text
+        let card = MessageCard::from_message(&serde_json::json!({"text": "hello"})).unwrap();
+        assert_eq!(card.text.as_deref(), Some("hello"));
+        assert_eq!(card.summary, "hello");
+
+        // The text is sent twice, as text and summary, so the MessageCard is too large even
+        // though the message isn't
+        let channel: TeamsChannelUrl = serde_json::from_value(serde_json::json!({
+            "url": "https://example.webhook.office.com/webhookb2/abc",
+            "format": "message_card",
+        }))
+        .unwrap();
+        let text = "a".repeat(validate::MAX_MESSAGE_BYTES * 2 / 3);
+        let err = render_message(&channel, serde_json::json!({"text": text})).unwrap_err();
+        assert!(matches!(
+            err.violations[..],
+            [validate::Violation::TooLarge { .. }]
+        ));
+
+        // A message with nothing to make a MessageCard from is refused rather than sent as is
+        let hero = serde_json::json!({"attachments": [{
+            "contentType": "application/vnd.microsoft.card.hero",
+            "content": {"title": "Hello"}
+        }]});
+        let err = render_message(&channel, hero).unwrap_err();
+        assert!(matches!(
+            err.violations[..],
+            [validate::Violation::NoMessageCard { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_card_validation() {
+        // Built-in layouts and plain text messages pass
+        let dms: DmsData = serde_json::from_value(dms_json()).unwrap();
+        validate::validate(&serde_json::to_value(AdaptiveCardData::from(dms)).unwrap()).unwrap();
+        let WebhookData::TypeA(build, _) =
+            parse_webhook_data(&HeaderMap::new(), buildkite_build_json()).unwrap()
+        else {
+            panic!("expected a Buildkite event");
+        };
+        validate::validate(&serde_json::to_value(AdaptiveCardData::from(*build)).unwrap()).unwrap();
+        validate::validate(&serde_json::json!({"text": "hello"})).unwrap();
+
+        let message = serde_json::to_value(AdaptiveCardData::raw(serde_json::json!({
+            "body": [
+                {"type": "TextBlock", "size": "huge"},
+                {"type": "textblock", "text": "case doesn't matter", "weight": "BOLDER"},
+                {"type": "Widget"},
+                {"type": "Widget", "fallback": {"type": "TextBlock", "text": "shown instead"}},
+                {"type": "Container", "items": [{"type": "Media", "sources": []}]}
+            ],
+            "actions": [{
+                "type": "Action.Submit",
+                "title": "Send",
+                "data": {"type": "not an element"}
+            }],
+            "msteams": {"entities": [{"type": "mention", "text": "<at>Ada</at>"}]}
+        })))
+        .unwrap();
+        let err = validate::validate(&message).unwrap_err();
+        let rules: Vec<String> = err
+            .violations
+            .iter()
+            .filter_map(|v| match v {
+                validate::Violation::Schema { path, .. } => Some(format!("schema {path}")),
+                validate::Violation::UnknownType { path, element } => {
+                    Some(format!("unknown {element} {path}"))
+                }
+                validate::Violation::Unsupported { path, element } => {
+                    Some(format!("unsupported {element} {path}"))
+                }
+                validate::Violation::TooLarge { .. }
+                | validate::Violation::NoMessageCard { .. } => None,
+            })
+            .collect();
+        let body = "/attachments/0/content/body";
+        assert!(rules.contains(&format!("schema {body}/0")), "{rules:?}");
+        assert!(
+            rules.contains(&format!("schema {body}/0/size")),
+            "{rules:?}"
+        );
+        assert!(
+            rules.contains(&format!("unknown textblock {body}/1")),
+            "{rules:?}"
+        );
+        assert!(
+            rules.contains(&format!("unknown Widget {body}/2")),
+            "{rules:?}"
+        );
+        assert!(
+            rules.contains(&format!("unsupported Media {body}/4/items/0")),
+            "{rules:?}"
+        );
+        assert!(
+            rules.contains(&"unsupported Action.Submit /attachments/0/content/actions/0".into())
+        );
+        assert!(!rules
+            .iter()
+            .any(|r| r.contains(&format!("{body}/1/")) || r.contains("/3")));
+        assert!(!rules
+            .iter()
+            .any(|r| r.contains("mention") || r.contains("not an element")));
+        assert_eq!(
+            serde_json::to_value(&err.violations[0]).unwrap()["rule"],
+            "schema"
+        );
+
+        // Too large to send, unless shortened to fit
+        let long = "word ".repeat(8000);
+        let facts: Vec<(String, String)> = (0..1000)
+            .map(|i| (format!("Fact {i}"), "value".to_string()))
+            .collect();
+        let card = builder::Card::new()
+            .title("Big", card::Color::Default)
+            .text(long.clone())
+            .facts(facts);
+        let mut message = serde_json::to_value(AdaptiveCardData::from(card)).unwrap();
+        let err = validate::validate(&message).unwrap_err();
+        assert!(matches!(
+            err.violations[..],
+            [validate::Violation::TooLarge {
+                limit: validate::MAX_MESSAGE_BYTES,
+                ..
+            }]
+        ));
+        assert!(validate::truncate(&mut message));
+        validate::validate(&message).unwrap();
+        let body = &message["attachments"][0]["content"]["body"];
+        assert_eq!(body[0]["text"], "Big");
+        let text = body[1]["text"].as_str().unwrap();
+        assert!(text.ends_with('…') && text.len() < long.len());
+        let facts = body[2]["facts"].as_array().unwrap();
+        assert_eq!(facts.last().unwrap()["title"], "…");
+        assert!(facts.len() > 1);
+        assert!(!validate::truncate(&mut message));
+    }
+
+    #[test]
+    fn test_buildkite_token_verification() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Buildkite-Token", "tok".parse().unwrap());
+        assert_eq!(
+            buildkite::verify_request(&headers, b"{}", Some("tok"), None, 0),
+            Ok(())
+        );
+        assert_eq!(
+            buildkite::verify_request(&headers, b"{}", Some("other"), None, 0),
+            Err(signature::SignatureError::Mismatch)
+        );
+        assert_eq!(
+            buildkite::verify_request(&headers, b"{}", None, None, 0),
+            Ok(())
+        );
+    }
+
     // // Integration tests
     // #[tokio::test]
     // async fn test_post_data() {
@@ -359,7 +2648,6 @@ mod tests {
 mod moar_tests {
     use super::*;
     use axum::{body::Body, http::Request};
-    use tokio;
     use tower::ServiceExt; // for `app.oneshot()` method
     use wiremock::matchers::{method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -403,4 +2691,557 @@ mod moar_tests {
         let body = std::str::from_utf8(&bytes).unwrap();
         assert_eq!(body, mock_response);
     }
+
+    #[tokio::test]
+    async fn test_delivery_queue() {
+        let mock_server = MockServer::start().await;
+        for (route, status, body) in [
+            ("/ok", 200, "1"),
+            ("/busy", 503, ""),
+            ("/refused", 400, "Bad payload"),
+        ] {
+            let mock = Mock::given(method("POST"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(status).set_body_string(body));
+            mock_server.register(mock).await;
+        }
+
+        let queue = queue::Queue::open(":memory:").unwrap();
+        let body = serde_json::json!({"text": "hello"});
+        for channel in ["ok", "busy", "refused"] {
+            let url = format!("{}/{channel}", mock_server.uri());
+            queue
+                .enqueue(channel, "text", &url, &body, None)
+                .await
+                .unwrap();
+        }
+        let client = reqwest::Client::new();
+        let due = queue.due().await.unwrap();
+        assert_eq!(due.len(), 3);
+        let mut outcomes = vec![];
+        for delivery in &due {
+            let outcome = queue::make_post_request(&client, delivery).await;
+            queue
+                .record(delivery, &outcome, Duration::ZERO)
+                .await
+                .unwrap();
+            outcomes.push(outcome);
+        }
+        assert_eq!(outcomes[0], queue::Outcome::Delivered(200));
+        assert!(matches!(
+            outcomes[1],
+            queue::Outcome::Retry {
+                status: Some(503),
+                ..
+            }
+        ));
+        assert_eq!(
+            outcomes[2],
+            queue::Outcome::Failed {
+                status: 400,
+                error: "Bad payload".to_string()
+            }
+        );
+        // The busy one waits out its backoff, the others are done
+        assert!(queue.due().await.unwrap().is_empty());
+
+        for attempts in 1..40 {
+            let delay = queue::backoff(attempts);
+            assert!(delay >= std::time::Duration::from_secs(1));
+            assert!(delay <= std::time::Duration::from_secs(15 * 60));
+        }
+        assert!(queue::backoff(4) >= std::time::Duration::from_secs(8));
+
+        // A destination that hangs doesn't hold up the others
+        let mock = Mock::given(method("POST"))
+            .and(path("/hanging"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)));
+        mock_server.register(mock).await;
+        let queue = queue::Queue::open(":memory:").unwrap();
+        let mut attempts = queue.subscribe();
+        for channel in ["hanging", "ok"] {
+            let url = format!("{}/{channel}", mock_server.uri());
+            queue
+                .enqueue(channel, "text", &url, &body, None)
+                .await
+                .unwrap();
+        }
+        tokio::spawn(queue.clone().run());
+        let attempt = tokio::time::timeout(Duration::from_secs(2), attempts.recv())
+            .await
+            .expect("the other destination isn't held up")
+            .unwrap();
+        assert_eq!(attempt.id, 2);
+        assert_eq!(attempt.status, queue::Status::Delivered);
+    }
+
+    #[tokio::test]
+    async fn test_throttling() {
+        use std::time::{Duration, Instant};
+
+        let mut rate_limit = rate_limit::RateLimiter::new(2.0, 60.0);
+        let start = Instant::now();
+        assert!(rate_limit.acquire("a", start).is_ok());
+        assert!(rate_limit.acquire("a", start).is_ok());
+        assert_eq!(rate_limit.acquire("a", start), Err(Duration::from_secs(1)));
+        // Other destinations have their own bucket
+        assert!(rate_limit.acquire("b", start).is_ok());
+        assert!(rate_limit
+            .acquire("a", start + Duration::from_secs(1))
+            .is_ok());
+
+        let mock_server = MockServer::start().await;
+        let mock = Mock::given(method("POST"))
+            .and(path("/throttled"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "120"));
+        mock_server.register(mock).await;
+        let mock = Mock::given(method("POST"))
+            .and(path("/connector"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "Microsoft Teams endpoint returned HTTP error 429 with ContextId ...",
+            ));
+        mock_server.register(mock).await;
+
+        let queue = queue::Queue::open(":memory:").unwrap();
+        let url = format!("{}/throttled", mock_server.uri());
+        for text in ["first", "second"] {
+            queue
+                .enqueue(
+                    "builds",
+                    "buildkite",
+                    &url,
+                    &serde_json::json!({ "text": text }),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let connector = format!("{}/connector", mock_server.uri());
+        queue
+            .enqueue(
+                "ops",
+                "text",
+                &connector,
+                &serde_json::json!({"text": "hello"}),
+                None,
+            )
+            .await
+            .unwrap();
+        // Only the oldest message to each destination is up
+        let due = queue.due().await.unwrap();
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].body["text"], "first");
+
+        let client = reqwest::Client::new();
+        let outcome = queue::make_post_request(&client, &due[0]).await;
+        assert_eq!(
+            outcome,
+            queue::Outcome::Throttled {
+                retry_after: Some(Duration::from_secs(120))
+            }
+        );
+        queue
+            .record(&due[0], &outcome, Duration::ZERO)
+            .await
+            .unwrap();
+        let outcome = queue::make_post_request(&client, &due[1]).await;
+        assert_eq!(outcome, queue::Outcome::Throttled { retry_after: None });
+        queue
+            .record(&due[1], &outcome, Duration::ZERO)
+            .await
+            .unwrap();
+
+        // Nothing is dropped or sent out of order while waiting
+        assert!(queue.due().await.unwrap().is_empty());
+        let mut backlog = queue.backlog().await.unwrap();
+        backlog.sort();
+        assert_eq!(backlog, [("builds".to_string(), 2), ("ops".to_string(), 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_results() {
+        let mock_server = MockServer::start().await;
+        for (route, status) in [
+            ("/webhookb2/ok", 200),
+            ("/webhookb2/refused", 400),
+            ("/webhookb2/down", 503),
+            ("/webhookb2/later", 200),
+        ] {
+            let mock = Mock::given(method("POST"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(status).set_body_string("1"));
+            mock_server.register(mock).await;
+        }
+        let base_url = format!("{}/webhookb2/", mock_server.uri());
+        let channel = |name: &str, path: &str, delivery_mode| TeamsChannelUrl {
+            name: name.to_string(),
+            url: format!("{base_url}{path}"),
+            delivery_mode,
+            ..Default::default()
+        };
+        let channels = vec![
+            channel("ok", "ok", DeliveryMode::Sync),
+            channel("refused", "refused", DeliveryMode::Sync),
+            channel("down", "down", DeliveryMode::Sync),
+            channel("later", "later", DeliveryMode::Async),
+        ];
+        let state = AppState {
+            whitelist: teams_urls_to_array(&channels),
+            channels,
+            base_url,
+            api_key: "secret".to_string(),
+            sns_certificates: sns::CertificateCache::default(),
+            mappings: HashMap::new(),
+            queue: queue::Queue::open(":memory:").unwrap(),
+        };
+        tokio::spawn(state.queue.clone().run());
+        let app = new_app(state);
+
+        let post = |path: &str, body: serde_json::Value| {
+            Request::builder()
+                .uri(path)
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let result = |response: Response| async move {
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<Value>(&bytes).unwrap_or_default(),
+            )
+        };
+        let text = serde_json::json!({"text": "hello"});
+
+        let response = app
+            .clone()
+            .oneshot(post("/webhookb2/ok", text.clone()))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "delivered");
+        assert_eq!(body["upstream_status"], 200);
+        assert!(body["id"].is_i64());
+
+        let response = app
+            .clone()
+            .oneshot(post("/webhookb2/refused", text.clone()))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body["status"], "dead_lettered");
+        assert_eq!(body["upstream_status"], 400);
+
+        // Still accepted when the first attempt failed but will be retried
+        let response = app
+            .clone()
+            .oneshot(post("/webhookb2/down", text.clone()))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["status"], "pending");
+        assert_eq!(body["upstream_status"], 503);
+        assert!(body["error"].is_string());
+
+        let response = app
+            .clone()
+            .oneshot(post("/webhookb2/later", text.clone()))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["status"], "pending");
+
+        // /check only names the channels for those with the apiKey
+        let check = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let (_, body) = result(app.clone().oneshot(check("/check")).await.unwrap()).await;
+        assert!(body["pending"].is_u64());
+        assert!(body.get("backlog").is_none());
+        let response = app.clone().oneshot(check("/check?apiKey=secret")).await;
+        let (_, body) = result(response.unwrap()).await;
+        assert!(body["backlog"]["later"].is_u64());
+
+        let response = app
+            .clone()
+            .oneshot(post("/webhookb2/unknown", text))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Payloads that can't be turned into a message never get an id
+        let response = app
+            .clone()
+            .oneshot(post(
+                "/webhookb2/ok",
+                serde_json::json!({"unexpected": true}),
+            ))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.get("id").is_none());
+
+        // The history shows what happened, without the channels' URLs
+        let get = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(get("/deliveries")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(get(
+                "/deliveries?apiKey=secret&source=text&status=dead_lettered",
+            ))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::OK);
+        let deliveries = body["deliveries"].as_array().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0]["channel"], "refused");
+        assert_eq!(deliveries[0]["attempts"], 1);
+        assert_eq!(deliveries[0]["last_status"], 400);
+        assert!(deliveries[0]["latency_ms"].is_i64());
+        assert!(!body.to_string().contains(&mock_server.uri()));
+
+        let response = app
+            .clone()
+            .oneshot(get("/deliveries?apiKey=secret&channel=ok"))
+            .await
+            .unwrap();
+        let (_, body) = result(response).await;
+        let id = body["deliveries"][0]["id"].as_i64().unwrap();
+        assert_eq!(body["deliveries"][0]["status"], "delivered");
+        let request = Request::builder()
+            .uri(format!("/deliveries/{id}"))
+            .header("X-Api-Key", "secret")
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = result(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["source"], "text");
+        assert_eq!(body["body"]["text"], "hello");
+        assert!(body.get("url").is_none());
+        let response = app
+            .oneshot(get("/deliveries/999?apiKey=secret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        // The channel's old URL refuses everything, as during an outage, and its new one
+        // takes it
+        let mock_server = MockServer::start().await;
+        for (route, status) in [("/webhookb2/outage", 400), ("/webhookb2/moved", 200)] {
+            let mock = Mock::given(method("POST"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(status).set_body_string("1"));
+            mock_server.register(mock).await;
+        }
+        let base_url = format!("{}/webhookb2/", mock_server.uri());
+        let queue = queue::Queue::open(":memory:").unwrap();
+        tokio::spawn(queue.clone().run());
+        let app = |format, url: &str| {
+            let channels = vec![TeamsChannelUrl {
+                name: "outage".to_string(),
+                url: format!("{base_url}{url}"),
+                format,
+                delivery_mode: DeliveryMode::Sync,
+                ..Default::default()
+            }];
+            new_app(AppState {
+                whitelist: teams_urls_to_array(&channels),
+                channels,
+                base_url: base_url.clone(),
+                api_key: "secret".to_string(),
+                sns_certificates: sns::CertificateCache::default(),
+                mappings: HashMap::new(),
+                queue: queue.clone(),
+            })
+        };
+        let post = |path: &str, body: serde_json::Value| {
+            Request::builder()
+                .uri(path)
+                .method("POST")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let result = |response: Response| async move {
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default(),
+            )
+        };
+
+        let before = app(MessageFormat::AdaptiveCard, "outage");
+        let text = serde_json::json!({"text": "hello"});
+        let ping = serde_json::json!({
+            "event": "ping",
+            "service": {"id": "svc", "provider": "webhook", "settings": {"url": "https://x"}},
+            "organization": {"name": "Acme"},
+            "sender": {"name": "Jo"}
+        });
+        let mut failed = vec![];
+        for payload in [text, ping] {
+            let response = before
+                .clone()
+                .oneshot(post("/webhookb2/outage", payload))
+                .await
+                .unwrap();
+            let (status, body) = result(response).await;
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+            assert_eq!(body["status"], "dead_lettered");
+            failed.push(body["id"].as_i64().unwrap());
+        }
+        // The payload is kept as it came, with what BuildkiteEvent doesn't model
+        let selection = queue::Selection {
+            ids: vec![failed[1]],
+            ..Default::default()
+        };
+        let stored = queue.replayable(&selection).await.unwrap();
+        assert_eq!(
+            stored[0].payload.as_ref().unwrap()["service"]["settings"]["url"],
+            "https://x"
+        );
+
+        // Replayed after the channel moved to MessageCards, which only the Buildkite message
+        // can be rendered again for, and to another URL, which every replay goes to
+        let after = app(MessageFormat::MessageCard, "moved");
+        let response = after
+            .clone()
+            .oneshot(post(
+                "/deliveries/replay",
+                serde_json::json!({"channel": "outage"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = after
+            .clone()
+            .oneshot(post(
+                "/deliveries/replay?apiKey=secret",
+                serde_json::json!({}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = after
+            .clone()
+            .oneshot(post(
+                "/deliveries/replay?apiKey=secret",
+                serde_json::json!({
+                    "channel": "outage",
+                    "status": "dead_lettered",
+                    "since": "2000-01-01T00:00:00Z",
+                    "rerender": true
+                }),
+            ))
+            .await
+            .unwrap();
+        let (status, body) = result(response).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["skipped"][0]["id"], failed[0]);
+        assert_eq!(
+            body["skipped"][0]["error"],
+            "Messages from text can't be rendered again"
+        );
+        let replayed = body["replayed"].as_array().unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0]["replay_of"], failed[1]);
+
+        let get = |path: String| {
+            Request::builder()
+                .uri(path)
+                .header("X-Api-Key", "secret")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let delivered = |id: &serde_json::Value| {
+            let (app, id) = (after.clone(), id.as_i64().unwrap());
+            async move {
+                let mut delivery = serde_json::Value::Null;
+                for _ in 0..50 {
+                    let response = app.clone().oneshot(get(format!("/deliveries/{id}")));
+                    delivery = result(response.await.unwrap()).await.1;
+                    if delivery["status"] == "delivered" {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                assert_eq!(delivery["status"], "delivered");
+                delivery
+            }
+        };
+        let deliveries = [delivered(&replayed[0]["id"]).await];
+        assert_eq!(deliveries[0]["replay_of"], failed[1]);
+        assert_eq!(deliveries[0]["body"]["@type"], "MessageCard");
+        assert_eq!(
+            deliveries[0]["body"]["title"],
+            "Buildkite webhook ping from Acme"
+        );
+
+        // The dead letters stay in the history, marked once replayed, and are only replayed
+        // again when asked for by id
+        let response = after
+            .clone()
+            .oneshot(get("/deliveries?status=dead_lettered".to_string()))
+            .await
+            .unwrap();
+        let dead_letters = result(response).await.1["deliveries"].clone();
+        assert_eq!(dead_letters[0]["id"], failed[1]);
+        assert_eq!(dead_letters[0]["replayed_by"], replayed[0]["id"]);
+        assert!(dead_letters[1].get("replayed_by").is_none());
+        let response = after
+            .clone()
+            .oneshot(post(
+                "/deliveries/replay?apiKey=secret",
+                serde_json::json!({"status": "dead_lettered"}),
+            ))
+            .await
+            .unwrap();
+        let replayed = result(response).await.1["replayed"].clone();
+        assert_eq!(replayed.as_array().unwrap().len(), 1);
+        assert_eq!(replayed[0]["replay_of"], failed[0]);
+        assert_eq!(delivered(&replayed[0]["id"]).await["body"]["text"], "hello");
+        let response = after
+            .oneshot(post(
+                "/deliveries/replay?apiKey=secret",
+                serde_json::json!({"ids": [failed[1], 999]}),
+            ))
+            .await
+            .unwrap();
+        let replayed = result(response).await.1["replayed"].clone();
+        assert_eq!(replayed.as_array().unwrap().len(), 1);
+        assert_eq!(replayed[0]["replay_of"], failed[1]);
+
+        let args = |args: &[&str]| {
+            parse_replay_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        let request = args(&[
+            "--ids",
+            "3,4",
+            "--since",
+            "2024-05-01T10:00:00Z",
+            "--rerender",
+        ]);
+        let request = request.unwrap();
+        assert_eq!(request.selection.ids, vec![3, 4]);
+        assert!(request.selection.since.is_some());
+        assert!(request.rerender);
+        assert!(args(&["--rerender"]).is_err());
+        assert!(args(&["--ids", "three"]).is_err());
+        assert!(args(&["--status", "lost"]).is_err());
+    }
 }
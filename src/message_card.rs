@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::card::{Action, AdaptiveCard, Color, Column, Element, FontWeight, ImageStyle, Inline};
+use crate::validate::CONTENT_TYPE;
+
+/// Legacy Office 365 connector card, for channels and consumers that can't show Adaptive Cards,
+/// see <https://learn.microsoft.com/outlook/actionable-messages/message-card-reference>
+///
+/// Rendered from the Adaptive Card a source produces, so every source has one: the bold title
+/// becomes the card title and its color the theme color, bylines become activity sections,
+/// text, facts and images go into sections in order, and link buttons become OpenUri actions.
+/// Inputs, media and other interactive parts have no MessageCard equivalent and are left out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageCard {
+    #[serde(rename = "@type")]
+    pub card_type: String,
+    #[serde(rename = "@context")]
+    pub context: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<Section>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub potential_action: Vec<PotentialAction>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Section {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity_subtitle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity_image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facts: Vec<Fact>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<SectionImage>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fact {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SectionImage {
+    pub image: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "@type")]
+pub enum PotentialAction {
+    OpenUri { name: String, targets: Vec<Target> },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub os: String,
+    pub uri: String,
+}
+
+impl Default for MessageCard {
+    fn default() -> Self {
+        MessageCard {
+            card_type: "MessageCard".to_string(),
+            context: "https://schema.org/extensions".to_string(),
+            summary: String::new(),
+            theme_color: None,
+            title: None,
+            text: None,
+            sections: vec![],
+            potential_action: vec![],
+        }
+    }
+}
+
+// Hex theme colors for the Adaptive Card colors; the default color leaves the theme alone.
+fn theme_color(color: Color) -> Option<String> {
+    let hex = match color {
+        Color::Default => return None,
+        Color::Dark => "323130",
+        Color::Light => "F3F2F1",
+        Color::Accent => "0078D7",
+        Color::Good => "2EA043",
+        Color::Warning => "D29922",
+        Color::Attention => "CF222E",
+    };
+    Some(hex.to_string())
+}
+
+fn inline_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::TextRun(run) => run.text.as_str(),
+            Inline::Text(text) => text.as_str(),
+        })
+        .collect()
+}
+
+// Text of the text blocks among `elements`, e.g. the name and subtitle of a byline.
+fn texts<'a>(elements: impl IntoIterator<Item = &'a Element>) -> Vec<String> {
+    elements
+        .into_iter()
+        .filter_map(|element| match element {
+            Element::TextBlock(text) => Some(text.text.clone()),
+            Element::RichTextBlock(text) => Some(inline_text(&text.inlines)),
+            _ => None,
+        })
+        .collect()
+}
+
+impl MessageCard {
+    // The section to add to, starting a new one when adding to this one would put things
+    // out of order: a section always shows its text before its facts and its facts before
+    // its images.
+    fn section(&mut self, fits: impl Fn(&Section) -> bool) -> &mut Section {
+        if !self.sections.last().is_some_and(fits) {
+            self.sections.push(Section::default());
+        }
+        self.sections.last_mut().unwrap()
+    }
+
+    fn add_text(&mut self, text: String) {
+        let section = self.section(|s| s.facts.is_empty() && s.images.is_empty());
+        section.text = Some(match section.text.take() {
+            Some(previous) => format!("{previous}\n\n{text}"),
+            None => text,
+        });
+    }
+
+    // A byline (an avatar, or a bold name over a subtitle) becomes an activity section; other
+    // column sets are read column by column.
+    fn add_columns(&mut self, columns: &[Column]) {
+        let items: Vec<&Element> = columns
+            .iter()
+            .flat_map(|column| column.items.iter().flatten())
+            .collect();
+        let avatar = items.iter().find_map(|item| match item {
+            Element::Image(image) if image.style == Some(ImageStyle::Person) => {
+                Some(image.url.clone())
+            }
+            _ => None,
+        });
+        let named = matches!(
+            items.as_slice(),
+            [Element::TextBlock(name), Element::TextBlock(_)]
+                if name.weight == Some(FontWeight::Bolder)
+        );
+        if avatar.is_some() || named {
+            let mut texts = texts(items).into_iter();
+            self.sections.push(Section {
+                activity_image: avatar,
+                activity_title: texts.next(),
+                activity_subtitle: texts.next(),
+                ..Default::default()
+            });
+        } else {
+            for item in items {
+                self.add_element(item);
+            }
+        }
+    }
+
+    fn add_element(&mut self, element: &Element) {
+        match element {
+            Element::TextBlock(text) => self.add_text(text.text.clone()),
+            Element::RichTextBlock(text) => self.add_text(inline_text(&text.inlines)),
+            Element::FactSet(facts) => {
+                let section = self.section(|s| s.images.is_empty());
+                section.facts.extend(facts.facts.iter().map(|fact| Fact {
+                    name: fact.title.clone(),
+                    value: fact.value.clone(),
+                }));
+            }
+            Element::Image(image) => self.section(|_| true).images.push(SectionImage {
+                image: image.url.clone(),
+                title: image.alt_text.clone(),
+            }),
+            Element::ImageSet(set) => {
+                for image in &set.images {
+                    self.add_element(&Element::Image(image.clone()));
+                }
+            }
+            Element::Container(container) => {
+                for item in &container.items {
+                    self.add_element(item);
+                }
+            }
+            Element::ColumnSet(set) => self.add_columns(set.columns.as_deref().unwrap_or_default()),
+            // Rows as facts, the first cell naming the row
+            Element::Table(table) => {
+                for row in table.rows.iter().flatten() {
+                    let mut cells = row
+                        .cells
+                        .iter()
+                        .flatten()
+                        .map(|cell| texts(&cell.items).join(" "));
+                    let name = cells.next().unwrap_or_default();
+                    let value = cells.collect::<Vec<_>>().join(" | ");
+                    self.section(|s| s.images.is_empty())
+                        .facts
+                        .push(Fact { name, value });
+                }
+            }
+            Element::ActionSet(set) => {
+                for action in &set.actions {
+                    self.add_action(action);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_action(&mut self, action: &Action) {
+        if let Action::OpenUrl(open) = action {
+            self.potential_action.push(PotentialAction::OpenUri {
+                name: open.props.title.clone().unwrap_or(open.url.clone()),
+                targets: vec![Target {
+                    os: "default".to_string(),
+                    uri: open.url.clone(),
+                }],
+            });
+        }
+    }
+
+    /// The MessageCard for a Teams message: its Adaptive Card converted, or its plain text.
+    /// An error saying why when the message has neither.
+    pub fn from_message(message: &Value) -> Result<MessageCard, String> {
+        let content = message
+            .get("attachments")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|attachment| attachment["contentType"] == CONTENT_TYPE)
+            .map(|attachment| attachment["content"].clone());
+        if let Some(content) = content {
+            let card: AdaptiveCard = serde_json::from_value(content)
+                .map_err(|err| format!("the Adaptive Card can't be read: {err}"))?;
+            return Ok(MessageCard::from(&card));
+        }
+        let text = message
+            .get("text")
+            .and_then(Value::as_str)
+            .ok_or("the message has neither an Adaptive Card nor text")?;
+        Ok(MessageCard {
+            summary: text.to_string(),
+            text: Some(text.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+impl From<&AdaptiveCard> for MessageCard {
+    fn from(card: &AdaptiveCard) -> Self {
+        let mut message = MessageCard::default();
+        let mut body = card.body.as_deref().unwrap_or_default();
+        if let Some((Element::TextBlock(title), rest)) = body.split_first() {
+            if title.weight == Some(FontWeight::Bolder) {
+                message.title = Some(title.text.clone());
+                message.theme_color = title.color.and_then(theme_color);
+                body = rest;
+            }
+        }
+        for element in body {
+            message.add_element(element);
+        }
+        for action in card.actions.iter().flatten() {
+            message.add_action(action);
+        }
+        // Connectors refuse cards without a summary or text
+        message.summary = message
+            .title
+            .clone()
+            .or(card.fallback_text.clone())
+            .or(message.sections.iter().find_map(|s| s.text.clone()))
+            .unwrap_or("Notification".to_string());
+        message
+    }
+}
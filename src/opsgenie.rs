@@ -0,0 +1,135 @@
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+
+// Opsgenie outgoing Webhook integration payload, sent for each alert action the integration
+// is set up to forward, see
+// https://support.atlassian.com/opsgenie/docs/integrate-opsgenie-with-outgoing-webhooks/
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpsgenieData {
+    pub action: Action,
+    pub alert: Alert,
+    pub integration_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Action {
+    Create,
+    Acknowledge,
+    UnAcknowledge,
+    Close,
+    EscalateNext,
+    AssignOwnership,
+    TakeOwnership,
+    AddNote,
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl Action {
+    // e.g. "acknowledged", appended to the alert title
+    pub fn description(&self) -> &str {
+        match self {
+            Action::Create => "created",
+            Action::Acknowledge => "acknowledged",
+            Action::UnAcknowledge => "unacknowledged",
+            Action::Close => "closed",
+            Action::EscalateNext => "escalated",
+            Action::AssignOwnership | Action::TakeOwnership => "assigned",
+            Action::AddNote => "noted",
+            Action::Other(action) => action,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Action::Create | Action::EscalateNext | Action::UnAcknowledge => Color::Attention,
+            Action::Acknowledge => Color::Warning,
+            Action::Close => Color::Good,
+            _ => Color::Accent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Responder {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub responder_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub alert_id: String,
+    pub tiny_id: Option<String>,
+    pub message: String,
+    pub description: Option<String>,
+    pub priority: Option<String>,
+    pub owner: Option<String>,
+    pub username: Option<String>,
+    pub entity: Option<String>,
+    pub source: Option<String>,
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub responders: Vec<Responder>,
+}
+
+const ALERT_URL: &str = "https://app.opsgenie.com/alert/detail";
+
+impl From<OpsgenieData> for AdaptiveCardData {
+    fn from(data: OpsgenieData) -> Self {
+        let alert = data.alert;
+        let title = match &alert.tiny_id {
+            Some(tiny_id) => format!("[#{tiny_id}] {}", alert.message),
+            None => alert.message.clone(),
+        };
+        let mut card = Card::new().title(
+            format!("{title} {}", data.action.description()),
+            data.action.color(),
+        );
+        if let Some(username) = alert.username.filter(|u| !u.is_empty()) {
+            card = card.detail(format!("By {username}"));
+        }
+        if let Some(note) = alert.note.filter(|n| !n.is_empty()) {
+            card = card.text(note);
+        } else if let Some(description) = alert.description.filter(|d| !d.is_empty()) {
+            card = card.text(description);
+        }
+        let mut facts = vec![];
+        if let Some(priority) = alert.priority {
+            facts.push(("Priority", priority));
+        }
+        if let Some(owner) = alert.owner.filter(|o| !o.is_empty()) {
+            facts.push(("Owner", owner));
+        }
+        let responders: Vec<String> = alert
+            .responders
+            .into_iter()
+            .filter_map(|r| r.name)
+            .collect();
+        if !responders.is_empty() {
+            facts.push(("Responders", responders.join(", ")));
+        }
+        if let Some(entity) = alert.entity.filter(|e| !e.is_empty()) {
+            facts.push(("Entity", entity));
+        }
+        if let Some(source) = alert.source.filter(|s| !s.is_empty()) {
+            facts.push(("Source", source));
+        }
+        if !alert.tags.is_empty() {
+            facts.push(("Tags", alert.tags.join(", ")));
+        }
+        card.facts(facts)
+            .open_url(
+                "Open in Opsgenie",
+                format!("{ALERT_URL}/{}/details", alert.alert_id),
+            )
+            .into()
+    }
+}
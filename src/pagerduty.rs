@@ -0,0 +1,189 @@
+use axum::http::HeaderMap;
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::signature::{self, SignatureError};
+
+// PagerDuty v3 webhook subscription payload.  Every delivery wraps a single event, see
+// https://developer.pagerduty.com/docs/webhooks/v3-overview/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PagerDutyData {
+    pub event: Event,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub id: Option<String>,
+    pub event_type: EventType,
+    pub resource_type: Option<String>,
+    pub occurred_at: Option<String>,
+    pub agent: Option<Reference>,
+    pub data: EventData,
+}
+
+// Incident lifecycle events.  Other event types (service and note events, pings) land in
+// Other and get a generic card.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum EventType {
+    #[serde(rename = "incident.triggered")]
+    Triggered,
+    #[serde(rename = "incident.acknowledged")]
+    Acknowledged,
+    #[serde(rename = "incident.unacknowledged")]
+    Unacknowledged,
+    #[serde(rename = "incident.escalated")]
+    Escalated,
+    #[serde(rename = "incident.reassigned")]
+    Reassigned,
+    #[serde(rename = "incident.reopened")]
+    Reopened,
+    #[serde(rename = "incident.resolved")]
+    Resolved,
+    #[serde(rename = "incident.priority_updated")]
+    PriorityUpdated,
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl EventType {
+    pub fn name(&self) -> &str {
+        match self {
+            EventType::Triggered => "triggered",
+            EventType::Acknowledged => "acknowledged",
+            EventType::Unacknowledged => "unacknowledged",
+            EventType::Escalated => "escalated",
+            EventType::Reassigned => "reassigned",
+            EventType::Reopened => "reopened",
+            EventType::Resolved => "resolved",
+            EventType::PriorityUpdated => "priority updated",
+            EventType::Other(event_type) => event_type,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            EventType::Triggered | EventType::Escalated | EventType::Reopened => Color::Attention,
+            EventType::Acknowledged => Color::Warning,
+            EventType::Resolved => Color::Good,
+            _ => Color::Accent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventData {
+    Incident(Box<Incident>),
+    #[serde(other)]
+    Other,
+}
+
+// PagerDuty's pointer to another object; `summary` is its display name.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reference {
+    pub id: Option<String>,
+    pub summary: Option<String>,
+    pub html_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: String,
+    pub number: Option<u64>,
+    pub title: String,
+    pub status: Option<String>,
+    pub urgency: Option<String>,
+    pub html_url: String,
+    pub service: Option<Reference>,
+    pub priority: Option<Reference>,
+    pub escalation_policy: Option<Reference>,
+    #[serde(default)]
+    pub assignees: Vec<Reference>,
+}
+
+// X-PagerDuty-Signature lists one `v1=<hex HMAC-SHA256 of the body>` per active secret,
+// comma separated, so a secret can be rotated without dropping deliveries.
+pub fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &str,
+) -> Result<(), SignatureError> {
+    let header = headers
+        .get("X-PagerDuty-Signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(SignatureError::MissingHeader("X-PagerDuty-Signature"))?;
+    let mut signatures = header
+        .split(',')
+        .filter_map(|s| s.trim().strip_prefix("v1="))
+        .peekable();
+    if signatures.peek().is_none() {
+        return Err(SignatureError::Malformed("X-PagerDuty-Signature"));
+    }
+    if signatures.any(|s| signature::verify_hmac_sha256_hex(secret.as_bytes(), body, s).is_ok()) {
+        Ok(())
+    } else {
+        Err(SignatureError::Mismatch)
+    }
+}
+
+fn summaries(references: &[Reference]) -> Option<String> {
+    let names: Vec<&str> = references
+        .iter()
+        .filter_map(|r| r.summary.as_deref())
+        .collect();
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+impl From<PagerDutyData> for AdaptiveCardData {
+    fn from(data: PagerDutyData) -> Self {
+        let event = data.event;
+        let EventData::Incident(incident) = event.data else {
+            return Card::new()
+                .title(
+                    format!("PagerDuty {}", event.event_type.name()),
+                    Color::Default,
+                )
+                .into();
+        };
+        let title = match incident.number {
+            Some(number) => format!("[#{number}] {}", incident.title),
+            None => incident.title.clone(),
+        };
+        let mut card = Card::new().title(
+            format!("{title} {}", event.event_type.name()),
+            event.event_type.color(),
+        );
+        if let Some(agent) = event.agent.as_ref().and_then(|a| a.summary.as_ref()) {
+            card = card.detail(format!("By {agent}"));
+        }
+        let mut facts = vec![];
+        if let Some(service) = incident.service.as_ref().and_then(|s| s.summary.clone()) {
+            facts.push(("Service", service));
+        }
+        if let Some(urgency) = incident.urgency {
+            facts.push(("Urgency", urgency));
+        }
+        if let Some(priority) = incident.priority.as_ref().and_then(|p| p.summary.clone()) {
+            facts.push(("Priority", priority));
+        }
+        if let Some(assignees) = summaries(&incident.assignees) {
+            facts.push(("Assigned to", assignees));
+        }
+        if let Some(policy) = incident
+            .escalation_policy
+            .as_ref()
+            .and_then(|p| p.summary.clone())
+        {
+            facts.push(("Escalation policy", policy));
+        }
+        card = card
+            .facts(facts)
+            .open_url("View incident", incident.html_url);
+        if let Some(url) = incident.service.and_then(|s| s.html_url) {
+            card = card.open_url("View service", url);
+        }
+        card.into()
+    }
+}
@@ -0,0 +1,786 @@
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::{broadcast, Notify},
+    task::JoinSet,
+};
+
+use crate::rate_limit::RateLimiter;
+
+// Attempts before a delivery is dead-lettered, unless DELIVERY_MAX_ATTEMPTS says otherwise.
+// With the backoff below the last one is about 45 minutes after the first.
+const MAX_ATTEMPTS: u32 = 12;
+const FIRST_RETRY: Duration = Duration::from_secs(2);
+const MAX_RETRY: Duration = Duration::from_secs(15 * 60);
+// Wait after being throttled without a Retry-After, and the longest Retry-After honoured
+const THROTTLED: Duration = Duration::from_secs(30);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+// How long the worker waits with nothing due, in case it missed being woken
+const IDLE: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// Delivered messages are kept in the history this long, unless DELIVERY_RETENTION_DAYS says
+// otherwise, and checked for hourly.  Dead letters are kept, replayed or not.
+const RETENTION_DAYS: i64 = 30;
+const PRUNE_EVERY: Duration = Duration::from_secs(60 * 60);
+// Longest error text kept from a failed attempt
+const MAX_ERROR_CHARS: usize = 500;
+
+// Schema changes, applied in order when the store is opened.  user_version holds how many
+// have been applied already, so new ones go at the end.
+const MIGRATIONS: [&str; 1] = ["
+    CREATE TABLE deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel TEXT NOT NULL,
+        source TEXT NOT NULL,
+        url TEXT NOT NULL,
+        body TEXT NOT NULL,
+        payload TEXT,
+        replay_of INTEGER,
+        replayed_by INTEGER,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL,
+        next_attempt_at INTEGER NOT NULL,
+        last_attempt_at INTEGER,
+        last_status INTEGER,
+        last_error TEXT,
+        latency_ms INTEGER
+    );
+    CREATE INDEX deliveries_due ON deliveries (status, next_attempt_at);
+    CREATE TABLE dead_letters (
+        id INTEGER PRIMARY KEY,
+        channel TEXT NOT NULL,
+        source TEXT NOT NULL,
+        url TEXT NOT NULL,
+        body TEXT NOT NULL,
+        payload TEXT,
+        replay_of INTEGER,
+        replayed_by INTEGER,
+        attempts INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        failed_at INTEGER NOT NULL,
+        last_status INTEGER,
+        last_error TEXT,
+        latency_ms INTEGER
+    );
+    "];
+
+// Outbound messages, stored in SQLite before the webhook that caused them is acknowledged and
+// sent by a background worker (`run`).  Deliveries to the same destination are sent in the
+// order they were queued, at the pace its rate limit allows.  Failed attempts are retried with
+// exponential backoff and jitter; deliveries that still fail after the last attempt, or that
+// Teams refuses outright, are moved to the dead_letters table.
+#[derive(Clone)]
+pub struct Queue {
+    db: Arc<Mutex<Connection>>,
+    wake: Arc<Notify>,
+    attempts: broadcast::Sender<Attempt>,
+    max_attempts: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    Delivered,
+    DeadLettered,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::Delivered => "delivered",
+            Status::DeadLettered => "dead_lettered",
+        }
+    }
+
+    fn parse(status: &str) -> Status {
+        match status {
+            "delivered" => Status::Delivered,
+            "dead_lettered" => Status::DeadLettered,
+            _ => Status::Pending,
+        }
+    }
+}
+
+// A delivery as shown by the history API.  Never includes the URL it goes to, which is as
+// good as a password.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    pub id: i64,
+    pub channel: String,
+    pub source: String,
+    pub status: Status,
+    pub attempts: u32,
+    // Teams' response to the last attempt
+    pub last_status: Option<u16>,
+    // How long the last attempt took
+    pub latency_ms: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: Option<String>,
+    pub last_attempt_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+    // The delivery this one replays
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<i64>,
+    // The latest delivery replaying this one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replayed_by: Option<i64>,
+    // The request body, only when a single delivery is asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+// Which deliveries to list, newest first.
+#[derive(Debug, Default, Deserialize)]
+pub struct Filter {
+    pub channel: Option<String>,
+    pub source: Option<String>,
+    pub status: Option<Status>,
+    pub limit: Option<u32>,
+}
+
+// Which delivered or dead-lettered deliveries to replay: those with one of the `ids`, created
+// from `since` until before `until`, for `channel` or with `status`.  Pending deliveries are
+// still being sent and are never replayed, and deliveries replayed already only when asked
+// for by id.
+#[derive(Debug, Default, Deserialize)]
+pub struct Selection {
+    #[serde(default)]
+    pub ids: Vec<i64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub channel: Option<String>,
+    pub status: Option<Status>,
+}
+
+impl Selection {
+    // Whether it says nothing, which would replay the whole history.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.channel.is_none()
+            && self.status.is_none()
+    }
+}
+
+// A delivery as stored, for replaying it.
+#[derive(Debug)]
+pub struct Stored {
+    pub id: i64,
+    pub channel: String,
+    pub source: String,
+    pub url: String,
+    pub body: Value,
+    // What the source sent, for the sources whose messages can be rendered again
+    pub payload: Option<Value>,
+}
+
+// Deliveries listed when the filter doesn't say, and the most it may ask for
+const LIST_LIMIT: u32 = 50;
+const MAX_LIST_LIMIT: u32 = 500;
+
+// Both tables as one, with the columns Record needs.
+const HISTORY: &str = "
+    SELECT id, channel, source, status, attempts, last_status, latency_ms, last_error,
+           created_at, last_attempt_at, next_attempt_at, body, replay_of, replayed_by
+    FROM deliveries
+    UNION ALL
+    SELECT id, channel, source, 'dead_lettered', attempts, last_status, latency_ms, last_error,
+           created_at, failed_at, NULL, body, replay_of, replayed_by
+    FROM dead_letters
+";
+
+fn timestamp(millis: Option<i64>) -> Option<String> {
+    DateTime::from_timestamp_millis(millis?).map(|time| time.to_rfc3339())
+}
+
+fn history_record(row: &rusqlite::Row, with_body: bool) -> rusqlite::Result<Record> {
+    let status: String = row.get(3)?;
+    let status = Status::parse(&status);
+    Ok(Record {
+        id: row.get(0)?,
+        channel: row.get(1)?,
+        source: row.get(2)?,
+        status,
+        attempts: row.get(4)?,
+        last_status: row.get(5)?,
+        latency_ms: row.get(6)?,
+        error: row.get(7)?,
+        created_at: timestamp(row.get(8)?),
+        last_attempt_at: timestamp(row.get(9)?),
+        next_attempt_at: timestamp(row.get(10)?).filter(|_| status == Status::Pending),
+        replay_of: row.get(12)?,
+        replayed_by: row.get(13)?,
+        body: match with_body {
+            true => row
+                .get::<_, String>(11)
+                .ok()
+                .and_then(|body| serde_json::from_str(&body).ok()),
+            false => None,
+        },
+    })
+}
+
+// Sent to subscribers after every attempt at a delivery.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    pub id: i64,
+    pub outcome: Outcome,
+    // Where that left the delivery
+    pub status: Status,
+}
+
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    pub id: i64,
+    pub channel: String,
+    pub url: String,
+    pub body: Value,
+    pub attempts: u32,
+}
+
+// What came of one attempt at a delivery.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Delivered(u16),
+    // Worth trying again: the request didn't get through, or Teams was unavailable or busy
+    Retry { status: Option<u16>, error: String },
+    // Teams refused the message, so sending it again won't help
+    Failed { status: u16, error: String },
+    // Teams asked for fewer messages.  Not counted as an attempt, so throttling never
+    // dead-letters a message.
+    Throttled { retry_after: Option<Duration> },
+}
+
+fn now() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn migrate(db: &mut Connection) -> rusqlite::Result<()> {
+    let applied: usize = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = db.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// Delay before the next attempt of a delivery that has had `attempts` so far: doubling from
+// FIRST_RETRY up to MAX_RETRY, less a random part of up to half so that deliveries that
+// failed together aren't all retried together.
+pub fn backoff(attempts: u32) -> Duration {
+    let delay = FIRST_RETRY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY);
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+// How long a 429 response asks to wait: a number of seconds or an HTTP date.
+fn retry_after(res: &reqwest::Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let wait = match value.parse() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => (DateTime::parse_from_rfc2822(value)
+            .ok()?
+            .with_timezone(&Utc)
+            - Utc::now())
+        .to_std()
+        .unwrap_or_default(),
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+fn error_text(error: impl ToString) -> String {
+    error.to_string().chars().take(MAX_ERROR_CHARS).collect()
+}
+
+// Sends one delivery to Teams.
+pub async fn make_post_request(client: &reqwest::Client, delivery: &Delivery) -> Outcome {
+    let res = match client.post(&delivery.url).json(&delivery.body).send().await {
+        Ok(res) => res,
+        // Without the URL, which is as good as a password
+        Err(err) => {
+            return Outcome::Retry {
+                status: None,
+                error: error_text(err.without_url()),
+            }
+        }
+    };
+    let status = res.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Outcome::Throttled {
+            retry_after: retry_after(&res),
+        };
+    }
+    let text = res.text().await.unwrap_or_default();
+    // Office 365 connectors answer 200 when they were throttled themselves
+    if status.is_success() && text.contains("HTTP error 429") {
+        Outcome::Throttled { retry_after: None }
+    } else if status.is_success() {
+        Outcome::Delivered(status.as_u16())
+    } else if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        Outcome::Retry {
+            status: Some(status.as_u16()),
+            error: error_text(text),
+        }
+    } else {
+        Outcome::Failed {
+            status: status.as_u16(),
+            error: error_text(text),
+        }
+    }
+}
+
+impl Queue {
+    // Opens (or creates) the store at `path`, or an in-memory one for ":memory:".
+    pub fn open(path: &str) -> rusqlite::Result<Queue> {
+        let mut db = Connection::open(path)?;
+        db.busy_timeout(Duration::from_secs(5))?;
+        migrate(&mut db)?;
+        let max_attempts = env::var("DELIVERY_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&attempts| attempts > 0)
+            .unwrap_or(MAX_ATTEMPTS);
+        Ok(Queue {
+            db: Arc::new(Mutex::new(db)),
+            wake: Arc::new(Notify::new()),
+            attempts: broadcast::channel(256).0,
+            max_attempts,
+        })
+    }
+
+    // Attempts from now on, for waiting on the outcome of a delivery.  Subscribe before
+    // queueing it, so its first attempt can't be missed.
+    pub fn subscribe(&self) -> broadcast::Receiver<Attempt> {
+        self.attempts.subscribe()
+    }
+
+    // Runs `query` with the connection on a blocking thread, as SQLite can keep it waiting:
+    // up to the busy timeout when another process, such as `lessons replay`, is writing.
+    async fn with_db<T, F>(&self, query: F) -> rusqlite::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || query(&mut db.lock().unwrap()))
+            .await
+            .expect("queries don't panic")
+    }
+
+    // Stores a message from `source` for `channel`, to be sent to `url` as soon as the worker
+    // gets to it.  `payload` is what the source sent, kept when the message can be rendered
+    // from it again.
+    pub async fn enqueue(
+        &self,
+        channel: &str,
+        source: &str,
+        url: &str,
+        body: &Value,
+        payload: Option<&Value>,
+    ) -> rusqlite::Result<i64> {
+        let row = (
+            channel.to_string(),
+            source.to_string(),
+            url.to_string(),
+            body.to_string(),
+            payload.map(Value::to_string),
+        );
+        let id = self
+            .with_db(move |db| {
+                db.execute(
+                    "INSERT INTO deliveries (channel, source, url, body, payload, created_at,
+                     next_attempt_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                    params![row.0, row.1, row.2, row.3, row.4, now()],
+                )?;
+                Ok(db.last_insert_rowid())
+            })
+            .await?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    // The oldest pending delivery to each destination, where its next attempt is due.  Later
+    // ones wait their turn, so a destination gets its messages in order.
+    pub async fn due(&self) -> rusqlite::Result<Vec<Delivery>> {
+        self.with_db(|db| {
+            let mut statement = db.prepare(
+                "SELECT id, channel, url, body, attempts FROM deliveries
+                 WHERE id IN (SELECT MIN(id) FROM deliveries WHERE status = 'pending' GROUP BY url)
+                 AND next_attempt_at <= ?1 ORDER BY id",
+            )?;
+            let rows = statement.query_map([now()], |row| {
+                let body: String = row.get(3)?;
+                Ok(Delivery {
+                    id: row.get(0)?,
+                    channel: row.get(1)?,
+                    url: row.get(2)?,
+                    body: serde_json::from_str(&body).unwrap_or_default(),
+                    attempts: row.get(4)?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // When the next pending delivery to a destination other than `sending` is due, in
+    // milliseconds since the epoch.
+    async fn next_attempt_at(&self, sending: &HashSet<String>) -> rusqlite::Result<Option<i64>> {
+        let sending = serde_json::json!(sending).to_string();
+        self.with_db(move |db| {
+            db.query_row(
+                "SELECT MIN(next_attempt_at) FROM deliveries WHERE status = 'pending'
+                 AND url NOT IN (SELECT value FROM json_each(?1))",
+                [sending],
+                |row| row.get(0),
+            )
+        })
+        .await
+    }
+
+    // Pending deliveries per channel.
+    pub async fn backlog(&self) -> rusqlite::Result<Vec<(String, u64)>> {
+        self.with_db(|db| {
+            let mut statement = db.prepare(
+                "SELECT channel, COUNT(*) FROM deliveries WHERE status = 'pending'
+                 GROUP BY channel",
+            )?;
+            let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // Puts off the next attempt at `delivery` by `wait`, without counting an attempt.
+    async fn defer(&self, delivery: &Delivery, wait: Duration) -> rusqlite::Result<()> {
+        let (id, at) = (delivery.id, now() + wait.as_millis() as i64);
+        self.with_db(move |db| {
+            db.execute(
+                "UPDATE deliveries SET next_attempt_at = ?2 WHERE id = ?1",
+                params![id, at],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    // Deliveries matching `filter`, newest first.
+    pub async fn list(&self, filter: &Filter) -> rusqlite::Result<Vec<Record>> {
+        let (channel, source) = (filter.channel.clone(), filter.source.clone());
+        let status = filter.status.map(Status::as_str);
+        let limit = filter.limit.unwrap_or(LIST_LIMIT).min(MAX_LIST_LIMIT);
+        self.with_db(move |db| {
+            let mut statement = db.prepare(&format!(
+                "SELECT * FROM ({HISTORY})
+                 WHERE (?1 IS NULL OR channel = ?1) AND (?2 IS NULL OR source = ?2)
+                 AND (?3 IS NULL OR status = ?3)
+                 ORDER BY id DESC LIMIT ?4"
+            ))?;
+            let rows = statement.query_map(params![channel, source, status, limit], |row| {
+                history_record(row, false)
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // One delivery, with the body that is or was sent.
+    pub async fn get(&self, id: i64) -> rusqlite::Result<Option<Record>> {
+        self.with_db(move |db| {
+            let mut statement = db.prepare(&format!("SELECT * FROM ({HISTORY}) WHERE id = ?1"))?;
+            let mut rows = statement.query_map([id], |row| history_record(row, true))?;
+            rows.next().transpose()
+        })
+        .await
+    }
+
+    // Delivered and dead-lettered deliveries matching `selection`, oldest first.
+    pub async fn replayable(&self, selection: &Selection) -> rusqlite::Result<Vec<Stored>> {
+        let ids = (!selection.ids.is_empty()).then(|| serde_json::json!(selection.ids).to_string());
+        let since = selection.since.map(|since| since.timestamp_millis());
+        let until = selection.until.map(|until| until.timestamp_millis());
+        let channel = selection.channel.clone();
+        let status = selection.status.map(Status::as_str);
+        self.with_db(move |db| {
+            let mut statement = db.prepare(
+                "SELECT id, channel, source, url, body, payload FROM (
+                    SELECT id, channel, source, url, body, payload, status, created_at,
+                           replayed_by
+                    FROM deliveries WHERE status = 'delivered'
+                    UNION ALL
+                    SELECT id, channel, source, url, body, payload, 'dead_lettered', created_at,
+                           replayed_by
+                    FROM dead_letters
+                 )
+                 WHERE (?1 IS NULL OR id IN (SELECT value FROM json_each(?1)))
+                 AND (?1 IS NOT NULL OR replayed_by IS NULL)
+                 AND (?2 IS NULL OR created_at >= ?2) AND (?3 IS NULL OR created_at < ?3)
+                 AND (?4 IS NULL OR channel = ?4) AND (?5 IS NULL OR status = ?5)
+                 ORDER BY id",
+            )?;
+            let rows = statement.query_map(params![ids, since, until, channel, status], |row| {
+                let body: String = row.get(4)?;
+                let payload: Option<String> = row.get(5)?;
+                Ok(Stored {
+                    id: row.get(0)?,
+                    channel: row.get(1)?,
+                    source: row.get(2)?,
+                    url: row.get(3)?,
+                    body: serde_json::from_str(&body).unwrap_or_default(),
+                    payload: payload.and_then(|payload| serde_json::from_str(&payload).ok()),
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // Queues `replays` as new deliveries, each noting the one it replays, and marks the
+    // originals as replayed by them.  Returns the new ids, in the same order.
+    pub async fn replay(&self, replays: Vec<Stored>) -> rusqlite::Result<Vec<i64>> {
+        let ids = self
+            .with_db(move |db| {
+                let now = now();
+                let tx = db.transaction()?;
+                let mut ids = vec![];
+                for replay in replays {
+                    tx.execute(
+                        "INSERT INTO deliveries (channel, source, url, body, payload, replay_of,
+                         created_at, next_attempt_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                        params![
+                            replay.channel,
+                            replay.source,
+                            replay.url,
+                            replay.body.to_string(),
+                            replay.payload.as_ref().map(Value::to_string),
+                            replay.id,
+                            now
+                        ],
+                    )?;
+                    let id = tx.last_insert_rowid();
+                    for table in ["deliveries", "dead_letters"] {
+                        tx.execute(
+                            &format!("UPDATE {table} SET replayed_by = ?2 WHERE id = ?1"),
+                            [replay.id, id],
+                        )?;
+                    }
+                    ids.push(id);
+                }
+                tx.commit()?;
+                Ok(ids)
+            })
+            .await?;
+        self.wake.notify_one();
+        Ok(ids)
+    }
+
+    // Forgets delivered messages older than the retention period.
+    async fn prune(&self) -> rusqlite::Result<usize> {
+        let days = env::var("DELIVERY_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&days: &i64| days > 0)
+            .unwrap_or(RETENTION_DAYS);
+        let before = now().saturating_sub(days.saturating_mul(24 * 60 * 60 * 1000));
+        self.with_db(move |db| {
+            db.execute(
+                "DELETE FROM deliveries WHERE status = 'delivered' AND created_at < ?1",
+                [before],
+            )
+        })
+        .await
+    }
+
+    // Records an attempt at `delivery` that took `latency`: marks it delivered, schedules the
+    // next attempt, or moves it to the dead letters.  Returns which of those it was.
+    pub async fn record(
+        &self,
+        delivery: &Delivery,
+        outcome: &Outcome,
+        latency: Duration,
+    ) -> rusqlite::Result<Status> {
+        let now = now();
+        let mut attempts = delivery.attempts + 1;
+        let (status, wait, last_status, error) = match outcome {
+            Outcome::Delivered(code) => (Status::Delivered, None, Some(*code), None),
+            Outcome::Throttled { retry_after } => {
+                attempts -= 1;
+                let wait = retry_after.unwrap_or(THROTTLED);
+                (Status::Pending, Some(wait), Some(429), Some("throttled"))
+            }
+            Outcome::Retry { status, error } if attempts < self.max_attempts => (
+                Status::Pending,
+                Some(backoff(attempts)),
+                *status,
+                Some(error.as_str()),
+            ),
+            Outcome::Retry { status, error } => {
+                (Status::DeadLettered, None, *status, Some(error.as_str()))
+            }
+            Outcome::Failed { status, error } => (
+                Status::DeadLettered,
+                None,
+                Some(*status),
+                Some(error.as_str()),
+            ),
+        };
+        let id = delivery.id;
+        let next_attempt_at = wait.map(|wait| now + wait.as_millis() as i64);
+        let error = error.map(str::to_string);
+        self.with_db(move |db| {
+            let tx = db.transaction()?;
+            tx.execute(
+                "UPDATE deliveries SET status = ?2, attempts = ?3,
+                 next_attempt_at = COALESCE(?4, next_attempt_at), last_status = ?5,
+                 last_error = ?6, last_attempt_at = ?7, latency_ms = ?8 WHERE id = ?1",
+                params![
+                    id,
+                    status.as_str(),
+                    attempts,
+                    next_attempt_at,
+                    last_status,
+                    error,
+                    now,
+                    latency.as_millis() as i64
+                ],
+            )?;
+            if status == Status::DeadLettered {
+                tx.execute(
+                    "INSERT INTO dead_letters (id, channel, source, url, body, payload, replay_of,
+                     attempts, created_at, failed_at, last_status, last_error, latency_ms)
+                     SELECT id, channel, source, url, body, payload, replay_of, attempts,
+                     created_at, last_attempt_at, last_status, last_error, latency_ms
+                     FROM deliveries WHERE id = ?1",
+                    [id],
+                )?;
+                tx.execute("DELETE FROM deliveries WHERE id = ?1", [id])?;
+            }
+            tx.commit()?;
+            Ok(status)
+        })
+        .await
+    }
+
+    // Logs and records an attempt at `delivery`, and tells the subscribers.
+    async fn finish(&self, delivery: Delivery, outcome: Outcome, latency: Duration) {
+        match &outcome {
+            Outcome::Delivered(_) => {
+                println!("Delivered {} to {}", delivery.id, delivery.channel)
+            }
+            Outcome::Throttled { retry_after } => println!(
+                "Delivery {} to {} was throttled, waiting {:?}",
+                delivery.id,
+                delivery.channel,
+                retry_after.unwrap_or(THROTTLED)
+            ),
+            Outcome::Retry { error, .. } | Outcome::Failed { error, .. } => println!(
+                "Delivery {} to {} failed (attempt {}): {error}",
+                delivery.id,
+                delivery.channel,
+                delivery.attempts + 1
+            ),
+        }
+        match self.record(&delivery, &outcome, latency).await {
+            // Nobody may be waiting, which is fine
+            Ok(status) => {
+                let _ = self.attempts.send(Attempt {
+                    id: delivery.id,
+                    outcome,
+                    status,
+                });
+            }
+            Err(err) => println!("Could not record delivery {}: {err}", delivery.id),
+        }
+    }
+
+    // Sends queued deliveries as they fall due, for as long as the adapter runs.  Deliveries
+    // to different destinations are sent side by side, so a slow one doesn't hold up the
+    // others, and one at a time to each destination.  Deliveries left pending by a restart
+    // are picked up again.
+    pub async fn run(self) {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("the HTTP client builds");
+        let mut rate_limit = RateLimiter::default();
+        let mut pruned: Option<Instant> = None;
+        let mut sending = JoinSet::new();
+        // Destinations with a delivery in `sending`
+        let mut busy: HashSet<String> = HashSet::new();
+        loop {
+            if pruned.is_none_or(|at| at.elapsed() >= PRUNE_EVERY) {
+                match self.prune().await {
+                    Ok(0) => {}
+                    Ok(count) => println!("Removed {count} old deliveries from the history"),
+                    Err(err) => println!("Could not prune the delivery history: {err}"),
+                }
+                pruned = Some(Instant::now());
+            }
+            let due = self.due().await.unwrap_or_else(|err| {
+                println!("Could not read the delivery queue: {err}");
+                vec![]
+            });
+            for delivery in due {
+                if busy.contains(&delivery.url) {
+                    continue;
+                }
+                if let Err(wait) = rate_limit.acquire(&delivery.url, Instant::now()) {
+                    if let Err(err) = self.defer(&delivery, wait).await {
+                        println!("Could not defer delivery {}: {err}", delivery.id);
+                    }
+                    continue;
+                }
+                busy.insert(delivery.url.clone());
+                let client = client.clone();
+                sending.spawn(async move {
+                    let started = Instant::now();
+                    let request = delivery.clone();
+                    // A panic while sending counts as a failed attempt, rather than leaving
+                    // the destination busy for good
+                    let outcome =
+                        tokio::spawn(async move { make_post_request(&client, &request).await })
+                            .await
+                            .unwrap_or_else(|err| Outcome::Retry {
+                                status: None,
+                                error: error_text(err),
+                            });
+                    (delivery, outcome, started.elapsed())
+                });
+            }
+            let wait = match self.next_attempt_at(&busy).await {
+                Ok(Some(at)) => Duration::from_millis((at - now()).max(0) as u64).min(IDLE),
+                _ => IDLE,
+            };
+            tokio::select! {
+                Some(sent) = sending.join_next() => match sent {
+                    Ok((delivery, outcome, latency)) => {
+                        busy.remove(&delivery.url);
+                        self.finish(delivery, outcome, latency).await;
+                    }
+                    Err(err) => println!("A delivery was not sent: {err}"),
+                },
+                _ = tokio::time::timeout(wait, self.wake.notified()) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    env,
+    time::{Duration, Instant},
+};
+
+// Teams throttles each connector or workflow on its own, at a few messages a second in short
+// bursts and fewer over longer windows, so by default a destination gets bursts of up to 4
+// messages and 30 a minute after that.
+const BURST: f64 = 4.0;
+const PER_MINUTE: f64 = 30.0;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// A token bucket per destination URL, so a burst to one channel doesn't hold up the others.
+// RATE_LIMIT_BURST and RATE_LIMIT_PER_MINUTE change the defaults.
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: HashMap<String, Bucket>,
+}
+
+fn env_rate(name: &str, default: f64) -> f64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&rate: &f64| rate > 0.0)
+        .unwrap_or(default)
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(
+            env_rate("RATE_LIMIT_BURST", BURST),
+            env_rate("RATE_LIMIT_PER_MINUTE", PER_MINUTE),
+        )
+    }
+}
+
+impl RateLimiter {
+    pub fn new(burst: f64, per_minute: f64) -> Self {
+        RateLimiter {
+            burst: burst.max(1.0),
+            per_second: per_minute / 60.0,
+            buckets: HashMap::new(),
+        }
+    }
+
+    // Takes a token for a message to `destination`, or says how long until there is one.
+    pub fn acquire(&mut self, destination: &str, now: Instant) -> Result<(), Duration> {
+        let bucket = self
+            .buckets
+            .entry(destination.to_string())
+            .or_insert(Bucket {
+                tokens: self.burst,
+                updated: now,
+            });
+        let refilled =
+            now.saturating_duration_since(bucket.updated).as_secs_f64() * self.per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use axum::http::HeaderMap;
+use lessons::builder::Card;
+use lessons::card::Color;
+use serde::{de::Error, Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::signature::{self, SignatureError};
+
+// Sentry integration platform webhooks.  The Sentry-Hook-Resource header names the resource
+// and the body carries it under `data`, see
+// https://docs.sentry.io/organization/integrations/integration-platform/webhooks/
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SentryEvent {
+    Issue(SentryHook<IssueData>),
+    EventAlert(SentryHook<EventAlertData>),
+    MetricAlert(SentryHook<MetricAlertData>),
+    Error(SentryHook<ErrorData>),
+}
+
+impl SentryEvent {
+    pub fn parse(
+        resource: &str,
+        data: serde_json::Value,
+    ) -> Result<SentryEvent, serde_json::Error> {
+        Ok(match resource {
+            "issue" => SentryEvent::Issue(serde_json::from_value(data)?),
+            "event_alert" => SentryEvent::EventAlert(serde_json::from_value(data)?),
+            "metric_alert" => SentryEvent::MetricAlert(serde_json::from_value(data)?),
+            "error" => SentryEvent::Error(serde_json::from_value(data)?),
+            _ => {
+                return Err(serde_json::Error::custom(format!(
+                    "unsupported Sentry resource {resource}"
+                )))
+            }
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SentryEvent::Issue(_) => "issue",
+            SentryEvent::EventAlert(_) => "event_alert",
+            SentryEvent::MetricAlert(_) => "metric_alert",
+            SentryEvent::Error(_) => "error",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SentryHook<T> {
+    pub action: String,
+    pub data: T,
+    pub actor: Option<Actor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Actor {
+    #[serde(rename = "type")]
+    pub actor_type: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Fatal,
+    Error,
+    Warning,
+    Info,
+    Debug,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub name: Option<String>,
+    pub slug: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub short_id: Option<String>,
+    pub title: String,
+    pub culprit: Option<String>,
+    pub level: Option<Level>,
+    pub status: Option<String>,
+    pub project: Option<Project>,
+    pub count: Option<String>,
+    pub user_count: Option<u64>,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    #[serde(rename = "web_url")]
+    pub web_url: Option<String>,
+    pub permalink: Option<String>,
+}
+
+// An individual occurrence, as sent for issue alerts and the error resource.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub event_id: Option<String>,
+    pub title: String,
+    pub culprit: Option<String>,
+    pub level: Option<Level>,
+    pub environment: Option<String>,
+    pub release: Option<String>,
+    pub datetime: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<(String, String)>,
+    pub web_url: Option<String>,
+    pub issue_url: Option<String>,
+}
+
+impl Event {
+    fn tag(&self, name: &str) -> Option<&String> {
+        self.tags.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssueData {
+    pub issue: Issue,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventAlertData {
+    pub event: Event,
+    pub triggered_rule: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorData {
+    pub error: Event,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricAlert {
+    pub title: Option<String>,
+    pub alert_rule: Option<AlertRule>,
+    pub date_started: Option<String>,
+    pub date_detected: Option<String>,
+    pub date_closed: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricAlertData {
+    pub metric_alert: MetricAlert,
+    pub description_title: Option<String>,
+    pub description_text: Option<String>,
+    pub web_url: Option<String>,
+}
+
+// Sentry-Hook-Signature is a hex HMAC-SHA256 of the body, keyed with the integration's
+// client secret.
+pub fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &str,
+) -> Result<(), SignatureError> {
+    let signature = headers
+        .get("Sentry-Hook-Signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(SignatureError::MissingHeader("Sentry-Hook-Signature"))?;
+    signature::verify_hmac_sha256_hex(secret.as_bytes(), body, signature)
+}
+
+fn color(action: &str, level: &Option<Level>) -> Color {
+    if action == "resolved" {
+        return Color::Good;
+    }
+    match level {
+        Some(Level::Fatal) | Some(Level::Error) => Color::Attention,
+        Some(Level::Warning) => Color::Warning,
+        _ => Color::Accent,
+    }
+}
+
+fn event_card(action: &str, event: Event, rule: Option<String>) -> AdaptiveCardData {
+    let mut card = Card::new().title(event.title.clone(), color(action, &event.level));
+    if let Some(culprit) = &event.culprit {
+        card = card.detail(culprit.clone());
+    }
+    let mut facts = vec![];
+    if let Some(environment) = event.environment.as_ref().or(event.tag("environment")) {
+        facts.push(("Environment", environment.clone()));
+    }
+    if let Some(release) = event.release.as_ref().or(event.tag("release")) {
+        facts.push(("Release", release.clone()));
+    }
+    if let Some(level) = &event.level {
+        facts.push(("Level", format!("{level:?}")));
+    }
+    if let Some(datetime) = &event.datetime {
+        facts.push(("Seen", datetime.clone()));
+    }
+    if let Some(rule) = rule {
+        facts.push(("Alert rule", rule));
+    }
+    card = card.facts(facts);
+    if let Some(url) = event.web_url.or(event.issue_url) {
+        card = card.open_url("Open in Sentry", url);
+    }
+    card.into()
+}
+
+impl From<SentryEvent> for AdaptiveCardData {
+    fn from(event: SentryEvent) -> Self {
+        match event {
+            SentryEvent::Issue(hook) => {
+                let issue = hook.data.issue;
+                let title = match &issue.short_id {
+                    Some(short_id) => format!("{short_id} {} {}", hook.action, issue.title),
+                    None => format!("Issue {} {}", hook.action, issue.title),
+                };
+                let mut card = Card::new().title(title, color(&hook.action, &issue.level));
+                if let Some(culprit) = &issue.culprit {
+                    card = card.detail(culprit.clone());
+                }
+                let mut facts = vec![];
+                if let Some(project) = &issue.project {
+                    facts.push(("Project", project.slug.clone()));
+                }
+                if let Some(count) = &issue.count {
+                    facts.push(("Events", count.clone()));
+                }
+                if let Some(user_count) = issue.user_count {
+                    facts.push(("Users", user_count.to_string()));
+                }
+                if let Some(first_seen) = &issue.first_seen {
+                    facts.push(("First seen", first_seen.clone()));
+                }
+                if let Some(last_seen) = &issue.last_seen {
+                    facts.push(("Last seen", last_seen.clone()));
+                }
+                if let Some(actor) = &hook.actor {
+                    facts.push(("By", actor.name.clone()));
+                }
+                card = card.facts(facts);
+                if let Some(url) = issue.web_url.or(issue.permalink) {
+                    card = card.open_url("Open in Sentry", url);
+                }
+                card.into()
+            }
+            SentryEvent::EventAlert(hook) => {
+                event_card(&hook.action, hook.data.event, hook.data.triggered_rule)
+            }
+            SentryEvent::Error(hook) => event_card(&hook.action, hook.data.error, None),
+            SentryEvent::MetricAlert(hook) => {
+                let alert = hook.data.metric_alert;
+                let color = match hook.action.as_str() {
+                    "critical" => Color::Attention,
+                    "warning" => Color::Warning,
+                    "resolved" => Color::Good,
+                    _ => Color::Accent,
+                };
+                let title = hook
+                    .data
+                    .description_title
+                    .or(alert.title)
+                    .or(alert.alert_rule.map(|r| r.name))
+                    .unwrap_or("Metric alert".to_string());
+                let mut card =
+                    Card::new().title(format!("[{}] {title}", hook.action.to_uppercase()), color);
+                if let Some(text) = hook.data.description_text {
+                    card = card.text(text);
+                }
+                let mut facts = vec![];
+                if let Some(started) = alert.date_started.or(alert.date_detected) {
+                    facts.push(("Started", started));
+                }
+                if let Some(closed) = alert.date_closed {
+                    facts.push(("Closed", closed));
+                }
+                card = card.facts(facts);
+                if let Some(url) = hook.data.web_url {
+                    card = card.open_url("Open in Sentry", url);
+                }
+                card.into()
+            }
+        }
+    }
+}
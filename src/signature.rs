@@ -0,0 +1,67 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// How far a signed timestamp may drift from our clock before we treat it as a replay.
+pub const REPLAY_WINDOW_SECS: u64 = 300;
+
+#[derive(Debug, PartialEq)]
+pub enum SignatureError {
+    MissingHeader(&'static str),
+    Malformed(&'static str),
+    Expired,
+    Mismatch,
+    // A payload that carries no signature, sent to a channel that has a source secret
+    Unsigned,
+    // The key to check the signature with could not be obtained, e.g. an SNS signing certificate
+    Certificate(String),
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::MissingHeader(header) => write!(f, "missing {header} header"),
+            SignatureError::Malformed(header) => write!(f, "malformed {header} header"),
+            SignatureError::Expired => write!(f, "signature timestamp outside replay window"),
+            SignatureError::Mismatch => write!(f, "signature mismatch"),
+            SignatureError::Unsigned => write!(f, "this channel only accepts signed webhooks"),
+            SignatureError::Certificate(reason) => {
+                write!(f, "unusable signing certificate: {reason}")
+            }
+        }
+    }
+}
+
+// Checks a hex-encoded HMAC-SHA256 of `message` without leaking timing information.
+pub fn verify_hmac_sha256_hex(
+    secret: &[u8],
+    message: &[u8],
+    signature_hex: &str,
+) -> Result<(), SignatureError> {
+    let expected = hex::decode(signature_hex.trim()).map_err(|_| SignatureError::Mismatch)?;
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(&expected)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn check_timestamp(timestamp: u64, now: u64) -> Result<(), SignatureError> {
+    if now.abs_diff(timestamp) > REPLAY_WINDOW_SECS {
+        return Err(SignatureError::Expired);
+    }
+    Ok(())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,300 @@
+use std::sync::OnceLock;
+
+use lessons::builder::Card;
+use lessons::card::Color;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_card::AdaptiveCardData;
+
+// Slack incoming webhook payload, so tools that can only post to Slack can post here instead.
+// Covers top-level mrkdwn text, legacy attachments and the common Block Kit blocks, see
+// https://api.slack.com/messaging/webhooks
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SlackMessage {
+    pub text: Option<String>,
+    pub username: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    pub color: Option<String>,
+    pub pretext: Option<String>,
+    pub author_name: Option<String>,
+    pub author_icon: Option<String>,
+    pub title: Option<String>,
+    pub title_link: Option<String>,
+    pub text: Option<String>,
+    pub fallback: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    pub image_url: Option<String>,
+    pub footer: Option<String>,
+    #[serde(default)]
+    pub actions: Vec<Element>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Field {
+    pub title: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Header {
+        text: Text,
+    },
+    Section {
+        text: Option<Text>,
+        #[serde(default)]
+        fields: Vec<Text>,
+        accessory: Option<Element>,
+    },
+    Context {
+        elements: Vec<Element>,
+    },
+    Actions {
+        elements: Vec<Element>,
+    },
+    Image {
+        image_url: String,
+        alt_text: Option<String>,
+    },
+    // Dividers and anything else without an Adaptive Card equivalent are dropped
+    #[serde(other)]
+    Unsupported,
+}
+
+// Slack composition text object; plain_text and mrkdwn only differ in whether markup applies.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Text {
+    #[serde(rename = "type")]
+    pub text_type: String,
+    pub text: String,
+}
+
+impl Text {
+    fn to_markdown(&self) -> String {
+        if self.text_type == "mrkdwn" {
+            mrkdwn_to_markdown(&self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+// Block elements and legacy attachment actions.  Context blocks hold text and images; action
+// blocks and accessories hold buttons, of which only link buttons mean anything in Teams.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button {
+        text: ButtonText,
+        url: Option<String>,
+    },
+    Image {
+        image_url: String,
+        alt_text: Option<String>,
+    },
+    Mrkdwn {
+        text: String,
+    },
+    PlainText {
+        text: String,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+// Block Kit buttons label themselves with a text object, legacy attachment actions with a
+// plain string.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ButtonText {
+    Object(Text),
+    Plain(String),
+}
+
+impl ButtonText {
+    fn as_str(&self) -> &str {
+        match self {
+            ButtonText::Object(text) => &text.text,
+            ButtonText::Plain(text) => text,
+        }
+    }
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+// Translate Slack mrkdwn into the markdown subset Adaptive Cards understand: links and
+// mentions lose their angle brackets, *bold* doubles up and ~strike~ is dropped.
+pub fn mrkdwn_to_markdown(text: &str) -> String {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+    static BOLD: OnceLock<Regex> = OnceLock::new();
+    static STRIKE: OnceLock<Regex> = OnceLock::new();
+    let text = regex(&LINK, r"<((?:https?|mailto):[^|>]+)(?:\|([^>]+))?>").replace_all(
+        text,
+        |caps: &regex::Captures| match caps.get(2) {
+            Some(label) => format!("[{}]({})", label.as_str(), &caps[1]),
+            None => caps[1].to_string(),
+        },
+    );
+    let text = regex(&MENTION, r"<([@#!])([^|>]+)(?:\|([^>]+))?>").replace_all(
+        &text,
+        |caps: &regex::Captures| {
+            let sigil = if &caps[1] == "#" { "#" } else { "@" };
+            format!("{sigil}{}", caps.get(3).map_or(&caps[2], |m| m.as_str()))
+        },
+    );
+    let text = regex(&BOLD, r"\*([^*\n]+)\*").replace_all(&text, "**$1**");
+    let text = regex(&STRIKE, r"~([^~\n]+)~").replace_all(&text, "$1");
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// Slack attachment colors are "good", "warning", "danger" or any hex color; hex colors are
+// matched to whichever Adaptive Card color they are closest in spirit to.
+fn attachment_color(color: Option<&str>) -> Color {
+    let Some(color) = color else {
+        return Color::Default;
+    };
+    match color {
+        "good" => return Color::Good,
+        "warning" => return Color::Warning,
+        "danger" => return Color::Attention,
+        _ => {}
+    }
+    let hex = color.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+    match (channel(0), channel(2), channel(4)) {
+        (Some(r), Some(g), Some(b)) if r > 0x80 && g > 0x80 && b < 0x80 => Color::Warning,
+        (Some(r), Some(g), Some(b)) if r > g && r > b => Color::Attention,
+        (Some(r), Some(g), Some(b)) if g > r && g > b => Color::Good,
+        (Some(_), Some(_), Some(_)) => Color::Accent,
+        _ => Color::Default,
+    }
+}
+
+// Link buttons become OpenUrl actions; other interactive elements have no Teams equivalent.
+fn element_action(card: Card, element: &Element) -> Card {
+    match element {
+        Element::Button {
+            text,
+            url: Some(url),
+        } => card.open_url(text.as_str(), url.clone()),
+        _ => card,
+    }
+}
+
+fn attachment_card(mut card: Card, attachment: Attachment) -> Card {
+    if let Some(pretext) = &attachment.pretext {
+        card = card.text(mrkdwn_to_markdown(pretext));
+    }
+    if let Some(author) = &attachment.author_name {
+        card = card.byline(author, attachment.author_icon.as_deref(), String::new());
+    }
+    let title = attachment
+        .title
+        .as_ref()
+        .map(|title| match &attachment.title_link {
+            Some(link) => format!("[{title}]({link})"),
+            None => title.clone(),
+        });
+    if let Some(title) = title {
+        card = card.title(title, attachment_color(attachment.color.as_deref()));
+    }
+    match (&attachment.text, &attachment.fallback) {
+        (Some(text), _) => card = card.text(mrkdwn_to_markdown(text)),
+        (None, Some(fallback)) if attachment.fields.is_empty() && attachment.title.is_none() => {
+            card = card.text(fallback.clone())
+        }
+        _ => {}
+    }
+    if !attachment.fields.is_empty() {
+        card = card.facts(
+            attachment
+                .fields
+                .iter()
+                .map(|f| (f.title.as_str(), mrkdwn_to_markdown(&f.value))),
+        );
+    }
+    if let Some(url) = attachment.image_url {
+        card = card.image(url, "Attachment image");
+    }
+    if let Some(footer) = &attachment.footer {
+        card = card.detail(mrkdwn_to_markdown(footer));
+    }
+    attachment.actions.iter().fold(card, element_action)
+}
+
+fn block_card(mut card: Card, block: Block) -> Card {
+    match block {
+        Block::Header { text } => card = card.title(text.text, Color::Default),
+        Block::Section {
+            text,
+            fields,
+            accessory,
+        } => {
+            if let Some(text) = text {
+                card = card.text(text.to_markdown());
+            }
+            for field in &fields {
+                card = card.text(field.to_markdown());
+            }
+            match accessory {
+                Some(Element::Image {
+                    image_url,
+                    alt_text,
+                }) => card = card.image(image_url, alt_text.as_deref().unwrap_or("Image")),
+                Some(element) => card = element_action(card, &element),
+                None => {}
+            }
+        }
+        Block::Context { elements } => {
+            let texts: Vec<String> = elements
+                .iter()
+                .filter_map(|e| match e {
+                    Element::Mrkdwn { text } => Some(mrkdwn_to_markdown(text)),
+                    Element::PlainText { text } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !texts.is_empty() {
+                card = card.detail(texts.join("  "));
+            }
+        }
+        Block::Actions { elements } => card = elements.iter().fold(card, element_action),
+        Block::Image {
+            image_url,
+            alt_text,
+        } => card = card.image(image_url, alt_text.as_deref().unwrap_or("Image")),
+        Block::Unsupported => {}
+    }
+    card
+}
+
+impl From<SlackMessage> for AdaptiveCardData {
+    fn from(message: SlackMessage) -> Self {
+        let mut card = Card::new();
+        // With blocks present Slack treats `text` as the notification fallback only
+        if let Some(text) = message.text.filter(|_| message.blocks.is_empty()) {
+            card = card.text(mrkdwn_to_markdown(&text));
+        }
+        card = message.blocks.into_iter().fold(card, block_card);
+        card = message.attachments.into_iter().fold(card, attachment_card);
+        card.into()
+    }
+}
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::adaptive_card::{self, AdaptiveCardData};
 // Convert data to correct json blob.  Example from DMS:
 // {
 //   "type": "snitch.reporting",
@@ -30,18 +33,81 @@ pub struct SnitchData {
 pub struct SnitchSubData {
     pub token: String,
     pub name: String,
-    pub notes: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
     pub tags: Vec<String>,
-    pub status: String,
-    pub previous_status: String,
+    pub status: SnitchStatus,
+    #[serde(default)]
+    pub previous_status: Option<SnitchStatus>,
 }
 
+// Alert types DMS sends to webhook integrations.  Anything we don't know about yet lands in
+// Other so it still reaches Teams as a generic notification.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum SnitchType {
     #[serde(rename = "snitch.missing")]
     Missing,
     #[serde(rename = "snitch.reporting")]
     Reporting,
+    #[serde(rename = "snitch.errored")]
+    Errored,
+    #[serde(rename = "snitch.failed")]
+    Failed,
     #[serde(rename = "snitch.paused")]
     Paused,
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl SnitchType {
+    // e.g. "is missing", completing a sentence that starts with the snitch name
+    pub fn description(&self) -> String {
+        match self {
+            SnitchType::Missing => "is missing".to_string(),
+            SnitchType::Reporting => "is reporting".to_string(),
+            SnitchType::Errored => "has errored".to_string(),
+            SnitchType::Failed => "has failed".to_string(),
+            SnitchType::Paused => "is paused".to_string(),
+            SnitchType::Other(snitch_type) => format!("sent {snitch_type}"),
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            SnitchType::Missing | SnitchType::Errored | SnitchType::Failed => "Attention",
+            SnitchType::Reporting => "Good",
+            SnitchType::Paused => "Warning",
+            SnitchType::Other(_) => "Default",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnitchStatus {
+    Pending,
+    Healthy,
+    Missing,
+    Errored,
+    Failed,
+    Paused,
+    #[serde(untagged)]
+    Other(String),
+}
+
+// The card for a snitch alert, laid out from `payload`, the webhook body it was parsed from.
+pub(crate) fn dms_card(data: &DmsData, payload: Value) -> AdaptiveCardData {
+    let view = serde_json::json!({
+        "description": data.snitch_type.description(),
+        "color": data.snitch_type.color(),
+    });
+    adaptive_card::from_template("dms", &adaptive_card::template_data(payload, view))
+}
+
+impl From<DmsData> for AdaptiveCardData {
+    fn from(data: DmsData) -> Self {
+        let payload = serde_json::to_value(&data).unwrap_or_default();
+        dms_card(&data, payload)
+    }
 }
@@ -0,0 +1,365 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::DateTime;
+use lessons::builder::Card;
+use lessons::card::Color;
+use regex::Regex;
+use reqwest::Url;
+use rsa::{
+    pkcs1v15::{Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+    signature::Verifier,
+    RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use x509_cert::{
+    der::{DecodePem, Encode},
+    Certificate,
+};
+
+use crate::adaptive_card::AdaptiveCardData;
+use crate::signature::{self, SignatureError};
+
+// How old a message may be.  SNS retries failed deliveries for a while, so this is longer
+// than the window for other signed webhooks, but a captured message can't be replayed
+// forever.
+const MAX_MESSAGE_AGE_SECS: u64 = 60 * 60;
+
+// Amazon SNS HTTP(S) delivery.  Every request is signed by SNS with the key in the
+// certificate at SigningCertURL, see
+// https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnsMessage {
+    #[serde(rename = "Type")]
+    pub message_type: MessageType,
+    #[serde(rename = "MessageId")]
+    pub message_id: String,
+    #[serde(rename = "Token")]
+    pub token: Option<String>,
+    #[serde(rename = "TopicArn")]
+    pub topic_arn: String,
+    #[serde(rename = "Subject")]
+    pub subject: Option<String>,
+    #[serde(rename = "Message")]
+    pub message: String,
+    #[serde(rename = "Timestamp")]
+    pub timestamp: String,
+    #[serde(rename = "SignatureVersion")]
+    pub signature_version: String,
+    #[serde(rename = "Signature")]
+    pub signature: String,
+    #[serde(rename = "SigningCertURL")]
+    pub signing_cert_url: String,
+    #[serde(rename = "SubscribeURL")]
+    pub subscribe_url: Option<String>,
+    #[serde(rename = "UnsubscribeURL")]
+    pub unsubscribe_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum MessageType {
+    SubscriptionConfirmation,
+    Notification,
+    UnsubscribeConfirmation,
+}
+
+impl SnsMessage {
+    // The "Key\nValue\n" string SNS signs.  Which keys take part depends on the message type,
+    // and Subject is only included on notifications that have one.
+    fn string_to_sign(&self) -> String {
+        let mut fields = vec![("Message", Some(&self.message))];
+        fields.push(("MessageId", Some(&self.message_id)));
+        match self.message_type {
+            MessageType::Notification => fields.push(("Subject", self.subject.as_ref())),
+            _ => fields.push(("SubscribeURL", self.subscribe_url.as_ref())),
+        }
+        fields.push(("Timestamp", Some(&self.timestamp)));
+        if self.message_type != MessageType::Notification {
+            fields.push(("Token", self.token.as_ref()));
+        }
+        fields.push(("TopicArn", Some(&self.topic_arn)));
+        let message_type = format!("{:?}", self.message_type);
+        fields.push(("Type", Some(&message_type)));
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|v| format!("{key}\n{v}\n")))
+            .collect()
+    }
+}
+
+// Only fetch signing certificates from SNS itself, otherwise anyone could sign a message with
+// a certificate they host.  SNS hosts are sns.<region>.amazonaws.com, so other amazonaws.com
+// hosts that happen to start with "sns.", such as S3 buckets, don't count.
+fn check_certificate_url(url: &str) -> Result<(), SignatureError> {
+    static SNS_HOST: OnceLock<Regex> = OnceLock::new();
+    let sns_host =
+        SNS_HOST.get_or_init(|| Regex::new(r"^sns\.[a-z0-9-]+\.amazonaws\.com(\.cn)?$").unwrap());
+    let parsed = Url::parse(url).map_err(|_| SignatureError::Malformed("SigningCertURL"))?;
+    let from_sns = sns_host.is_match(parsed.host_str().unwrap_or_default());
+    if parsed.scheme() != "https" || !from_sns || !parsed.path().ends_with(".pem") {
+        return Err(SignatureError::Certificate(format!(
+            "{url} is not an SNS URL"
+        )));
+    }
+    Ok(())
+}
+
+// Public keys of SNS signing certificates by URL.  SNS rotates its certificates rarely, so
+// each one is only downloaded the first time a message refers to it.  Tests preload fixtures
+// with insert_pem.
+#[derive(Clone, Default)]
+pub struct CertificateCache {
+    keys: Arc<Mutex<HashMap<String, RsaPublicKey>>>,
+}
+
+impl CertificateCache {
+    pub fn insert_pem(&self, url: &str, pem: &str) -> Result<(), SignatureError> {
+        let invalid = |err: x509_cert::der::Error| SignatureError::Certificate(err.to_string());
+        let certificate = Certificate::from_pem(pem.as_bytes()).map_err(invalid)?;
+        let der = certificate
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .map_err(invalid)?;
+        let key = RsaPublicKey::from_public_key_der(&der)
+            .map_err(|err| SignatureError::Certificate(err.to_string()))?;
+        self.keys.lock().unwrap().insert(url.to_string(), key);
+        Ok(())
+    }
+
+    async fn key(&self, url: &str) -> Result<RsaPublicKey, SignatureError> {
+        if let Some(key) = self.keys.lock().unwrap().get(url) {
+            return Ok(key.clone());
+        }
+        println!("Fetching SNS signing certificate {url}");
+        let fetch = |err: reqwest::Error| SignatureError::Certificate(err.to_string());
+        let pem = reqwest::get(url)
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(fetch)?
+            .text()
+            .await
+            .map_err(fetch)?;
+        self.insert_pem(url, &pem)?;
+        Ok(self.keys.lock().unwrap()[url].clone())
+    }
+
+    pub async fn verify(&self, message: &SnsMessage) -> Result<(), SignatureError> {
+        self.verify_at(message, signature::unix_now()).await
+    }
+
+    // Checks the message as of `now`, in seconds since the epoch.
+    pub async fn verify_at(&self, message: &SnsMessage, now: u64) -> Result<(), SignatureError> {
+        check_certificate_url(&message.signing_cert_url)?;
+        check_message_age(&message.timestamp, now)?;
+        let key = self.key(&message.signing_cert_url).await?;
+        let signature = BASE64
+            .decode(&message.signature)
+            .ok()
+            .and_then(|bytes| Signature::try_from(bytes.as_slice()).ok())
+            .ok_or(SignatureError::Malformed("Signature"))?;
+        let signed = message.string_to_sign();
+        let result = match message.signature_version.as_str() {
+            "1" => VerifyingKey::<sha1::Sha1>::new(key).verify(signed.as_bytes(), &signature),
+            "2" => VerifyingKey::<sha2::Sha256>::new(key).verify(signed.as_bytes(), &signature),
+            _ => return Err(SignatureError::Malformed("SignatureVersion")),
+        };
+        result.map_err(|_| SignatureError::Mismatch)
+    }
+}
+
+// Messages from the future are allowed the usual clock drift.
+fn check_message_age(timestamp: &str, now: u64) -> Result<(), SignatureError> {
+    let sent = DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .and_then(|sent| u64::try_from(sent.timestamp()).ok())
+        .ok_or(SignatureError::Malformed("Timestamp"))?;
+    if sent > now + signature::REPLAY_WINDOW_SECS || now.saturating_sub(sent) > MAX_MESSAGE_AGE_SECS
+    {
+        return Err(SignatureError::Expired);
+    }
+    Ok(())
+}
+
+// CloudWatch alarm state change, delivered as the Message of an SNS notification, see
+// https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/AlarmThatSendsEmail.html
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CloudWatchAlarm {
+    pub alarm_name: String,
+    pub alarm_description: Option<String>,
+    #[serde(rename = "AWSAccountId")]
+    pub aws_account_id: Option<String>,
+    pub new_state_value: AlarmState,
+    pub new_state_reason: Option<String>,
+    pub old_state_value: Option<AlarmState>,
+    pub state_change_time: Option<String>,
+    pub region: Option<String>,
+    pub alarm_arn: Option<String>,
+    pub trigger: Option<Trigger>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlarmState {
+    Alarm,
+    Ok,
+    InsufficientData,
+}
+
+impl AlarmState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AlarmState::Alarm => "ALARM",
+            AlarmState::Ok => "OK",
+            AlarmState::InsufficientData => "INSUFFICIENT_DATA",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            AlarmState::Alarm => Color::Attention,
+            AlarmState::Ok => Color::Good,
+            AlarmState::InsufficientData => Color::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dimension {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Trigger {
+    pub metric_name: Option<String>,
+    pub namespace: Option<String>,
+    pub statistic: Option<String>,
+    #[serde(default)]
+    pub dimensions: Vec<Dimension>,
+    pub period: Option<u64>,
+    pub evaluation_periods: Option<u64>,
+    pub comparison_operator: Option<String>,
+    pub threshold: Option<f64>,
+}
+
+impl CloudWatchAlarm {
+    fn metric(&self) -> Option<String> {
+        let trigger = self.trigger.as_ref()?;
+        let mut metric = [trigger.namespace.as_deref(), trigger.metric_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !trigger.dimensions.is_empty() {
+            let dimensions: Vec<String> = trigger
+                .dimensions
+                .iter()
+                .map(|d| format!("{}={}", d.name, d.value))
+                .collect();
+            metric.push_str(&format!(" ({})", dimensions.join(", ")));
+        }
+        Some(metric)
+    }
+
+    fn threshold(&self) -> Option<String> {
+        let trigger = self.trigger.as_ref()?;
+        let threshold = trigger.threshold?;
+        let mut text = match (&trigger.statistic, &trigger.comparison_operator) {
+            (Some(statistic), Some(operator)) => format!("{statistic} {operator} {threshold}"),
+            (None, Some(operator)) => format!("{operator} {threshold}"),
+            _ => threshold.to_string(),
+        };
+        if let (Some(periods), Some(period)) = (trigger.evaluation_periods, trigger.period) {
+            text.push_str(&format!(" for {periods} x {period}s"));
+        }
+        Some(text)
+    }
+
+    // Region code from the alarm ARN, arn:aws:cloudwatch:<region>:<account>:alarm:<name>
+    fn region_code(&self) -> Option<&str> {
+        self.alarm_arn.as_deref()?.split(':').nth(3)
+    }
+
+    fn console_url(&self) -> Option<String> {
+        let region = self.region_code()?;
+        let mut url = Url::parse(&format!(
+            "https://console.aws.amazon.com/cloudwatch/home?region={region}"
+        ))
+        .ok()?;
+        url.set_fragment(Some(&format!("alarmsV2:alarm/{}", self.alarm_name)));
+        Some(url.to_string())
+    }
+}
+
+impl From<CloudWatchAlarm> for AdaptiveCardData {
+    fn from(alarm: CloudWatchAlarm) -> Self {
+        let state = &alarm.new_state_value;
+        let mut card = Card::new().title(
+            format!("{}: {}", state.as_str(), alarm.alarm_name),
+            state.color(),
+        );
+        if let Some(description) = alarm.alarm_description.as_ref().filter(|d| !d.is_empty()) {
+            card = card.text(description.clone());
+        }
+        if let Some(reason) = &alarm.new_state_reason {
+            card = card.detail(reason.clone());
+        }
+        let mut facts = vec![];
+        facts.push((
+            "State",
+            match &alarm.old_state_value {
+                Some(old) => format!("{} → {}", old.as_str(), state.as_str()),
+                None => state.as_str().to_string(),
+            },
+        ));
+        if let Some(metric) = alarm.metric() {
+            facts.push(("Metric", metric));
+        }
+        if let Some(threshold) = alarm.threshold() {
+            facts.push(("Threshold", threshold));
+        }
+        if let Some(region) = alarm
+            .region
+            .clone()
+            .or(alarm.region_code().map(String::from))
+        {
+            facts.push(("Region", region));
+        }
+        if let Some(account) = &alarm.aws_account_id {
+            facts.push(("Account", account.clone()));
+        }
+        if let Some(time) = &alarm.state_change_time {
+            facts.push(("Changed", time.clone()));
+        }
+        card = card.facts(facts);
+        if let Some(url) = alarm.console_url() {
+            card = card.open_url("View in CloudWatch", url);
+        }
+        card.into()
+    }
+}
+
+// Notifications that are not CloudWatch alarms are shown as they are.
+impl From<SnsMessage> for AdaptiveCardData {
+    fn from(message: SnsMessage) -> Self {
+        if let Ok(alarm) = serde_json::from_str::<CloudWatchAlarm>(&message.message) {
+            return alarm.into();
+        }
+        let topic = message.topic_arn.rsplit(':').next().unwrap_or_default();
+        let title = message
+            .subject
+            .unwrap_or(format!("Notification from {topic}"));
+        Card::new()
+            .title(title, Color::Default)
+            .text(message.message)
+            .detail(format!("Topic: {}", message.topic_arn))
+            .into()
+    }
+}
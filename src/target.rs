@@ -0,0 +1,80 @@
+use lessons::builder::{AdaptiveCardData, Card};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+
+// What a channel URL expects in the request body.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestFormat {
+    // Office 365 connector (webhookb2): a Teams message, plain {"text": ...} or with card
+    // attachments
+    Connector,
+    // Workflows "Post to a channel when a webhook request is received": a Teams message, but
+    // only with card attachments, so plain text is sent as a card
+    Workflow,
+    // The Adaptive Card alone, for flows that post their request body as a card themselves
+    Card,
+}
+
+impl RequestFormat {
+    // Workflow for Workflows URLs, connector for anything else.
+    pub fn for_url(url: &str) -> Self {
+        if is_workflow_url(url) {
+            RequestFormat::Workflow
+        } else {
+            RequestFormat::Connector
+        }
+    }
+}
+
+// Power Automate HTTP trigger URLs, as created by the Workflows app: on logic.azure.com, or
+// on an environment's api.powerplatform.com host for newer flows.
+pub fn is_workflow_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .is_some_and(|host| {
+            host.ends_with(".logic.azure.com") || host.ends_with(".api.powerplatform.com")
+        })
+}
+
+// A Workflows URL is only accepted with the `sig` it was created with.
+pub fn has_signature(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.query_pairs().any(|(key, _)| key == "sig"))
+}
+
+// The part of an Office 365 connector URL after webhookb2/, which callers of /webhookb2 use.
+pub fn connector_path(url: &str) -> Option<&str> {
+    url.split_once("/webhookb2/").map(|(_, path)| path)
+}
+
+// A plain {"text": ...} message as a card, for targets that only take cards.  Anything else
+// is returned as it is.
+pub fn card_message(message: Value) -> Value {
+    if message.get("attachments").is_some() {
+        return message;
+    }
+    match message.get("text").and_then(Value::as_str) {
+        Some(text) => {
+            serde_json::to_value(AdaptiveCardData::from(Card::new().text(text))).unwrap_or(message)
+        }
+        None => message,
+    }
+}
+
+// The request body for a target taking `format`, from the Teams message to send.
+pub fn request_body(format: RequestFormat, message: Value) -> Value {
+    match format {
+        RequestFormat::Connector => message,
+        RequestFormat::Workflow => card_message(message),
+        RequestFormat::Card => match card_message(message) {
+            Value::Object(mut message) => message
+                .get_mut("attachments")
+                .and_then(|attachments| attachments.get_mut(0))
+                .map(|attachment| attachment["content"].take())
+                .unwrap_or(Value::Object(message)),
+            message => message,
+        },
+    }
+}
@@ -0,0 +1,857 @@
+use std::{cmp::Ordering, fmt};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, Timelike, Utc};
+use serde_json::{Map, Value};
+use serde_json_path::JsonPath;
+
+// Adaptive Card Templating, see https://learn.microsoft.com/adaptive-cards/templating/language
+//
+// A template is an ordinary card whose strings may hold `${expression}` bindings, evaluated
+// against a JSON data payload.  Objects can rebind their data with `$data` (an array repeats
+// the object once per entry, with `$index` counting from 0) and drop out with a falsy `$when`.
+// A string that is a single binding keeps the JSON type of its result, anything else is
+// interpolated as text.  Properties the data doesn't have evaluate to null rather than an
+// error, so optional fields only need a `$when`.
+//
+// Expressions cover the parts of the Adaptive Expression Language cards tend to use: property
+// paths, `$root`, `$data` and `$index`, string/number/boolean/null literals, the usual
+// arithmetic, comparison and logical operators, and the functions listed in `call`, plus
+// `jsonPath(path)` for JSONPath lookups on the data (or `jsonPath(value, path)` on a value).
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+fn error<T>(message: impl Into<String>) -> Result<T, TemplateError> {
+    Err(TemplateError(message.into()))
+}
+
+struct Scope<'a> {
+    root: &'a Value,
+    data: Value,
+    index: Option<usize>,
+}
+
+// Expands `template` against `data`.  A root object whose `$when` is false expands to null.
+pub fn expand(template: &Value, data: &Value) -> Result<Value, TemplateError> {
+    let scope = Scope {
+        root: data,
+        data: data.clone(),
+        index: None,
+    };
+    Ok(expand_value(template, &scope)?.unwrap_or(Value::Null))
+}
+
+// Checks every expression in `template` parses and only calls known functions, by evaluating
+// it without data, so mistakes show up before there is a payload to expand it against.
+// Unlike `expand`, expressions under a `$when` are checked whatever it says.
+pub fn check(template: &Value) -> Result<(), TemplateError> {
+    match template {
+        Value::String(text) => {
+            let scope = Scope {
+                root: &Value::Null,
+                data: Value::Null,
+                index: None,
+            };
+            interpolate(text, &scope).map(|_| ())
+        }
+        Value::Array(items) => items.iter().try_for_each(check),
+        Value::Object(fields) => fields.values().try_for_each(check),
+        _ => Ok(()),
+    }
+}
+
+// The nodes of `value` that a JSONPath such as `$.incident.components[*].name` matches: null
+// for none, the node for one, and an array of them for several.
+pub fn json_path(value: &Value, path: &str) -> Result<Value, TemplateError> {
+    let parsed =
+        JsonPath::parse(path).or_else(|err| error(format!("invalid path {path}: {err}")))?;
+    let nodes = parsed.query(value).all();
+    Ok(match nodes.as_slice() {
+        [] => Value::Null,
+        [node] => (*node).clone(),
+        nodes => Value::Array(nodes.iter().map(|&node| node.clone()).collect()),
+    })
+}
+
+// None when the value is an object dropped by its `$when`.
+fn expand_value(template: &Value, scope: &Scope) -> Result<Option<Value>, TemplateError> {
+    match template {
+        Value::Object(fields) => match fields.get("$data") {
+            Some(binding) => {
+                let scope = Scope {
+                    root: scope.root,
+                    data: bind(binding, scope)?,
+                    index: scope.index,
+                };
+                expand_fields(fields, &scope)
+            }
+            None => expand_fields(fields, scope),
+        },
+        Value::Array(items) => {
+            let mut expanded = vec![];
+            for item in items {
+                expand_item(item, scope, &mut expanded)?;
+            }
+            Ok(Some(Value::Array(expanded)))
+        }
+        Value::String(text) => interpolate(text, scope).map(Some),
+        _ => Ok(Some(template.clone())),
+    }
+}
+
+// Array entries are where `$data` bound to an array repeats its object.
+fn expand_item(
+    item: &Value,
+    scope: &Scope,
+    expanded: &mut Vec<Value>,
+) -> Result<(), TemplateError> {
+    let Some(fields) = item.as_object() else {
+        expanded.extend(expand_value(item, scope)?);
+        return Ok(());
+    };
+    let Some(binding) = fields.get("$data") else {
+        expanded.extend(expand_fields(fields, scope)?);
+        return Ok(());
+    };
+    match bind(binding, scope)? {
+        Value::Array(entries) => {
+            for (index, data) in entries.into_iter().enumerate() {
+                let scope = Scope {
+                    root: scope.root,
+                    data,
+                    index: Some(index),
+                };
+                expanded.extend(expand_fields(fields, &scope)?);
+            }
+        }
+        data => {
+            let scope = Scope {
+                root: scope.root,
+                data,
+                index: scope.index,
+            };
+            expanded.extend(expand_fields(fields, &scope)?);
+        }
+    }
+    Ok(())
+}
+
+fn bind(binding: &Value, scope: &Scope) -> Result<Value, TemplateError> {
+    Ok(expand_value(binding, scope)?.unwrap_or(Value::Null))
+}
+
+fn expand_fields(
+    fields: &Map<String, Value>,
+    scope: &Scope,
+) -> Result<Option<Value>, TemplateError> {
+    if let Some(when) = fields.get("$when") {
+        if !truthy(&bind(when, scope)?) {
+            return Ok(None);
+        }
+    }
+    let mut expanded = Map::new();
+    for (key, value) in fields {
+        if key == "$data" || key == "$when" {
+            continue;
+        }
+        if let Some(value) = expand_value(value, scope)? {
+            expanded.insert(key.clone(), value);
+        }
+    }
+    Ok(Some(Value::Object(expanded)))
+}
+
+// Splits text into literal runs and `${...}` bindings, skipping over braces inside quoted
+// strings so `${join(tags, '}')}` ends where it should.  An unterminated `${` is literal.
+fn segments(text: &str) -> Vec<(bool, &str)> {
+    let mut segments = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let body = &rest[start + 2..];
+        let mut depth = 0;
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in body.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') if depth == 0 => {
+                    end = Some(i);
+                    break;
+                }
+                (None, '}') => depth -= 1,
+                _ => {}
+            }
+        }
+        let Some(end) = end else { break };
+        if start > 0 {
+            segments.push((false, &rest[..start]));
+        }
+        segments.push((true, &body[..end]));
+        rest = &body[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push((false, rest));
+    }
+    segments
+}
+
+fn interpolate(text: &str, scope: &Scope) -> Result<Value, TemplateError> {
+    let segments = segments(text);
+    if let [(true, expression)] = segments.as_slice() {
+        return evaluate(expression, scope);
+    }
+    let mut interpolated = String::new();
+    for (is_expression, segment) in segments {
+        if is_expression {
+            interpolated.push_str(&to_text(&evaluate(segment, scope)?));
+        } else {
+            interpolated.push_str(segment);
+        }
+    }
+    Ok(Value::String(interpolated))
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty() && text != "false",
+        _ => true,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(Value),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 20] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]",
+    ",", ".",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = vec![];
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\'' || c == '"' {
+            let Some(end) = rest[1..].find(c) else {
+                return error(format!("unterminated string in {expression}"));
+            };
+            tokens.push(Token::Str(rest[1..end + 1].to_string()));
+            end + 2
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number = &rest[..len];
+            let value = match number.parse::<i64>() {
+                Ok(number) => Value::from(number),
+                Err(_) => match number.parse::<f64>() {
+                    Ok(number) => Value::from(number),
+                    Err(_) => return error(format!("invalid number {number}")),
+                },
+            };
+            tokens.push(Token::Number(value));
+            len
+        } else if c.is_alphabetic() || c == '_' || c == '$' || c == '@' {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '$' && c != '@')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            return error(format!("unexpected {c:?} in {expression}"));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn evaluate(expression: &str, scope: &Scope) -> Result<Value, TemplateError> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+        scope,
+    };
+    let value = parser.expression()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(value),
+        Some(token) => error(format!("unexpected {token:?} in {expression}")),
+    }
+}
+
+// Recursive descent parser that evaluates as it goes; expressions have no side effects, so
+// evaluating both branches of `if` or `&&` is harmless.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    scope: &'a Scope<'a>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.tokens.get(self.position) == Some(&Token::Op(op)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), TemplateError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            error(format!("expected {op}"))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            left = Value::Bool(truthy(&left) || truthy(&right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.equality()?;
+        while self.eat("&&") {
+            let right = self.equality()?;
+            left = Value::Bool(truthy(&left) && truthy(&right));
+        }
+        Ok(left)
+    }
+
+    fn equality(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.comparison()?;
+        loop {
+            if self.eat("==") {
+                left = Value::Bool(equals(&left, &self.comparison()?));
+            } else if self.eat("!=") {
+                left = Value::Bool(!equals(&left, &self.comparison()?));
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.additive()?;
+        loop {
+            let accept: fn(Ordering) -> bool = if self.eat("<=") {
+                Ordering::is_le
+            } else if self.eat(">=") {
+                Ordering::is_ge
+            } else if self.eat("<") {
+                Ordering::is_lt
+            } else if self.eat(">") {
+                Ordering::is_gt
+            } else {
+                return Ok(left);
+            };
+            let right = self.additive()?;
+            left = Value::Bool(compare(&left, &right).is_some_and(accept));
+        }
+    }
+
+    fn additive(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            if self.eat("+") {
+                let right = self.multiplicative()?;
+                left = if left.is_string() || right.is_string() {
+                    Value::String(to_text(&left) + &to_text(&right))
+                } else {
+                    arithmetic("+", &left, &right)?
+                };
+            } else if self.eat("-") {
+                let right = self.multiplicative()?;
+                left = arithmetic("-", &left, &right)?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Value, TemplateError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                "*"
+            } else if self.eat("/") {
+                "/"
+            } else if self.eat("%") {
+                "%"
+            } else {
+                return Ok(left);
+            };
+            let right = self.unary()?;
+            left = arithmetic(op, &left, &right)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, TemplateError> {
+        if self.eat("!") {
+            Ok(Value::Bool(!truthy(&self.unary()?)))
+        } else if self.eat("-") {
+            arithmetic("-", &Value::from(0), &self.unary()?)
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Value, TemplateError> {
+        let mut value = self.primary()?;
+        loop {
+            if self.eat(".") {
+                match self.next() {
+                    Some(Token::Ident(key)) => value = member(&value, &Value::String(key)),
+                    _ => return error("expected a property name after ."),
+                }
+            } else if self.eat("[") {
+                let key = self.expression()?;
+                self.expect("]")?;
+                value = member(&value, &key);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, TemplateError> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Str(text)) => Ok(Value::String(text)),
+            Some(Token::Op("(")) => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) if self.eat("(") => {
+                let mut args = vec![];
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.expression()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                match (name.as_str(), args.as_slice()) {
+                    ("jsonPath", [path]) => json_path(self.scope.root, &to_text(path)),
+                    ("jsonPath", [value, path]) => json_path(value, &to_text(path)),
+                    _ => call(&name, args),
+                }
+            }
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                "$root" => self.scope.root.clone(),
+                "$data" => self.scope.data.clone(),
+                "$index" => self.scope.index.map_or(Value::Null, Value::from),
+                _ => member(&self.scope.data, &Value::String(name)),
+            }),
+            Some(token) => error(format!("unexpected {token:?}")),
+            None => error("unexpected end of expression"),
+        }
+    }
+}
+
+fn member(value: &Value, key: &Value) -> Value {
+    match (value, key) {
+        (Value::Object(fields), Value::String(key)) => fields.get(key).cloned(),
+        (Value::Array(items), Value::Number(index)) => index
+            .as_u64()
+            .and_then(|index| items.get(index as usize).cloned()),
+        _ => None,
+    }
+    .unwrap_or(Value::Null)
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(left), Some(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        _ => left.as_f64()?.partial_cmp(&right.as_f64()?),
+    }
+}
+
+fn number(value: &Value) -> Result<f64, TemplateError> {
+    match value {
+        Value::Number(number) => Ok(number.as_f64().unwrap_or_default()),
+        Value::String(text) => text
+            .trim()
+            .parse()
+            .or_else(|_| error(format!("{text:?} is not a number"))),
+        Value::Null => Ok(0.0),
+        value => error(format!("{value} is not a number")),
+    }
+}
+
+// Integer results stay integers so `${count + 1}` doesn't render as "3.0".
+fn from_f64(number: f64) -> Value {
+    if number.fract() == 0.0 && number.abs() < i64::MAX as f64 {
+        Value::from(number as i64)
+    } else {
+        Value::from(number)
+    }
+}
+
+fn arithmetic(op: &str, left: &Value, right: &Value) -> Result<Value, TemplateError> {
+    let (left, right) = (number(left)?, number(right)?);
+    Ok(from_f64(match op {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        "/" if right == 0.0 => return error("division by zero"),
+        "/" => left / right,
+        _ => left % right,
+    }))
+}
+
+fn count(value: &Value) -> usize {
+    match value {
+        Value::String(text) => text.chars().count(),
+        Value::Array(items) => items.len(),
+        Value::Object(fields) => fields.len(),
+        _ => 0,
+    }
+}
+
+fn text_arg(args: &[Value], index: usize) -> String {
+    args.get(index).map(to_text).unwrap_or_default()
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value, TemplateError> {
+    let arity = |min: usize, max: usize| {
+        if args.len() < min || args.len() > max {
+            error(format!(
+                "{name} takes {min} to {max} arguments, got {}",
+                args.len()
+            ))
+        } else {
+            Ok(())
+        }
+    };
+    let arg = |index: usize| args.get(index).cloned().unwrap_or(Value::Null);
+    Ok(match name {
+        "if" => {
+            arity(3, 3)?;
+            if truthy(&args[0]) {
+                arg(1)
+            } else {
+                arg(2)
+            }
+        }
+        "equals" => {
+            arity(2, 2)?;
+            Value::Bool(equals(&args[0], &args[1]))
+        }
+        "not" => {
+            arity(1, 1)?;
+            Value::Bool(!truthy(&args[0]))
+        }
+        "and" => Value::Bool(args.iter().all(truthy)),
+        "or" => Value::Bool(args.iter().any(truthy)),
+        "exists" => {
+            arity(1, 1)?;
+            Value::Bool(!args[0].is_null())
+        }
+        "empty" => {
+            arity(1, 1)?;
+            Value::Bool(args[0].is_null() || (!args[0].is_number() && count(&args[0]) == 0))
+        }
+        "count" | "length" => {
+            arity(1, 1)?;
+            Value::from(count(&args[0]))
+        }
+        "concat" if !args.is_empty() && args.iter().all(Value::is_array) => Value::Array(
+            args.into_iter()
+                .flat_map(|arg| arg.as_array().cloned().unwrap_or_default())
+                .collect(),
+        ),
+        "concat" => Value::String(args.iter().map(to_text).collect()),
+        "join" => {
+            arity(2, 3)?;
+            let items: Vec<String> = match &args[0] {
+                Value::Array(items) => items.iter().map(to_text).collect(),
+                Value::Null => vec![],
+                value => vec![to_text(value)],
+            };
+            let separator = text_arg(&args, 1);
+            match (items.split_last(), args.get(2)) {
+                (Some((last, init)), Some(last_separator)) if !init.is_empty() => {
+                    Value::String(init.join(&separator) + &to_text(last_separator) + last)
+                }
+                _ => Value::String(items.join(&separator)),
+            }
+        }
+        "first" | "last" => {
+            arity(1, 1)?;
+            match &args[0] {
+                Value::Array(items) if name == "first" => items.first().cloned(),
+                Value::Array(items) => items.last().cloned(),
+                Value::String(text) if name == "first" => {
+                    text.chars().next().map(|c| Value::from(c.to_string()))
+                }
+                Value::String(text) => text.chars().last().map(|c| Value::from(c.to_string())),
+                _ => None,
+            }
+            .unwrap_or(Value::Null)
+        }
+        "contains" => {
+            arity(2, 2)?;
+            Value::Bool(match &args[0] {
+                Value::Array(items) => items.iter().any(|item| equals(item, &args[1])),
+                Value::Object(fields) => fields.contains_key(&to_text(&args[1])),
+                value => to_text(value).contains(&to_text(&args[1])),
+            })
+        }
+        "startsWith" => {
+            arity(2, 2)?;
+            Value::Bool(text_arg(&args, 0).starts_with(&text_arg(&args, 1)))
+        }
+        "endsWith" => {
+            arity(2, 2)?;
+            Value::Bool(text_arg(&args, 0).ends_with(&text_arg(&args, 1)))
+        }
+        "toUpper" => {
+            arity(1, 1)?;
+            Value::String(text_arg(&args, 0).to_uppercase())
+        }
+        "toLower" => {
+            arity(1, 1)?;
+            Value::String(text_arg(&args, 0).to_lowercase())
+        }
+        "trim" => {
+            arity(1, 1)?;
+            Value::String(text_arg(&args, 0).trim().to_string())
+        }
+        "substring" => {
+            arity(2, 3)?;
+            let text = text_arg(&args, 0);
+            let start = number(&args[1])? as usize;
+            let chars = text.chars().skip(start);
+            Value::String(match args.get(2) {
+                Some(length) => chars.take(number(length)? as usize).collect(),
+                None => chars.collect(),
+            })
+        }
+        "replace" => {
+            arity(3, 3)?;
+            Value::String(text_arg(&args, 0).replace(&text_arg(&args, 1), &text_arg(&args, 2)))
+        }
+        "split" => {
+            arity(2, 2)?;
+            let separator = text_arg(&args, 1);
+            Value::Array(
+                text_arg(&args, 0)
+                    .split(separator.as_str())
+                    .map(Value::from)
+                    .collect(),
+            )
+        }
+        "string" => {
+            arity(1, 1)?;
+            Value::String(to_text(&args[0]))
+        }
+        "int" => {
+            arity(1, 1)?;
+            Value::from(number(&args[0])?.trunc() as i64)
+        }
+        "float" => {
+            arity(1, 1)?;
+            Value::from(number(&args[0])?)
+        }
+        "bool" => {
+            arity(1, 1)?;
+            Value::Bool(truthy(&args[0]))
+        }
+        "json" => {
+            arity(1, 1)?;
+            match &args[0] {
+                Value::String(text) => {
+                    serde_json::from_str(text).or_else(|err| error(format!("json: {err}")))?
+                }
+                value => value.clone(),
+            }
+        }
+        "add" | "sub" | "mul" | "div" | "mod" => {
+            arity(2, 2)?;
+            let op = match name {
+                "add" => "+",
+                "sub" => "-",
+                "mul" => "*",
+                "div" => "/",
+                _ => "%",
+            };
+            arithmetic(op, &args[0], &args[1])?
+        }
+        "max" | "min" => {
+            arity(1, usize::MAX)?;
+            let numbers = match args.as_slice() {
+                [Value::Array(items)] => items.iter().map(number).collect::<Result<Vec<_>, _>>()?,
+                args => args.iter().map(number).collect::<Result<Vec<_>, _>>()?,
+            };
+            let pick = if name == "max" { f64::max } else { f64::min };
+            numbers
+                .into_iter()
+                .reduce(pick)
+                .map_or(Value::Null, from_f64)
+        }
+        "formatNumber" => {
+            arity(2, 2)?;
+            let precision = number(&args[1])? as usize;
+            Value::String(format!("{:.*}", precision, number(&args[0])?))
+        }
+        "formatDateTime" => {
+            arity(1, 2)?;
+            if args[0].is_null() {
+                return Ok(Value::Null);
+            }
+            let timestamp = parse_timestamp(&args[0])?;
+            Value::String(match args.get(1) {
+                Some(format) => format_timestamp(&timestamp, &to_text(format)),
+                None => timestamp
+                    .with_timezone(&Utc)
+                    .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                    .to_string(),
+            })
+        }
+        "jsonPath" => {
+            return error(format!(
+                "jsonPath takes 1 to 2 arguments, got {}",
+                args.len()
+            ))
+        }
+        _ => return error(format!("unknown function {name}")),
+    })
+}
+
+// ISO 8601 timestamps, the "2024-05-01 10:00:00 UTC" form Buildkite uses, and Unix seconds.
+fn parse_timestamp(value: &Value) -> Result<DateTime<FixedOffset>, TemplateError> {
+    let parsed = match value {
+        Value::Number(seconds) => seconds
+            .as_f64()
+            .and_then(|seconds| DateTime::from_timestamp(seconds as i64, 0))
+            .map(|timestamp| timestamp.fixed_offset()),
+        Value::String(text) => DateTime::parse_from_rfc3339(text).ok().or_else(|| {
+            let text = text.trim_end_matches(" UTC").trim_end_matches('Z');
+            ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+                .map(|timestamp| timestamp.and_utc().fixed_offset())
+        }),
+        _ => None,
+    };
+    match parsed {
+        Some(timestamp) => Ok(timestamp),
+        None => error(format!("formatDateTime: cannot parse {value}")),
+    }
+}
+
+// .NET style custom format strings, which is what formatDateTime takes: yyyy, MM, MMM, dd,
+// ddd, HH, hh, mm, ss, fff, tt and zzz, with quoted or backslash-escaped literals.
+fn format_timestamp(timestamp: &DateTime<FixedOffset>, format: &str) -> String {
+    let chars: Vec<char> = format.chars().collect();
+    let mut formatted = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let run = chars[i..].iter().take_while(|&&other| other == c).count();
+        i += run;
+        let hour12 = (timestamp.hour() + 11) % 12 + 1;
+        match (c, run) {
+            ('\'' | '"', _) => {
+                i -= run - 1;
+                while i < chars.len() && chars[i] != c {
+                    formatted.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+            }
+            ('\\', _) => {
+                i -= run - 1;
+                formatted.extend(chars.get(i));
+                i += 1;
+            }
+            ('y', 1) => formatted += &(timestamp.year() % 100).to_string(),
+            ('y', 2) => formatted += &format!("{:02}", timestamp.year() % 100),
+            ('y', _) => formatted += &format!("{:0run$}", timestamp.year()),
+            ('M', 1) => formatted += &timestamp.month().to_string(),
+            ('M', 2) => formatted += &format!("{:02}", timestamp.month()),
+            ('M', 3) => formatted += &timestamp.format("%b").to_string(),
+            ('M', _) => formatted += &timestamp.format("%B").to_string(),
+            ('d', 1) => formatted += &timestamp.day().to_string(),
+            ('d', 2) => formatted += &format!("{:02}", timestamp.day()),
+            ('d', 3) => formatted += &timestamp.format("%a").to_string(),
+            ('d', _) => formatted += &timestamp.format("%A").to_string(),
+            ('H', 1) => formatted += &timestamp.hour().to_string(),
+            ('H', _) => formatted += &format!("{:02}", timestamp.hour()),
+            ('h', 1) => formatted += &hour12.to_string(),
+            ('h', _) => formatted += &format!("{hour12:02}"),
+            ('m', 1) => formatted += &timestamp.minute().to_string(),
+            ('m', _) => formatted += &format!("{:02}", timestamp.minute()),
+            ('s', 1) => formatted += &timestamp.second().to_string(),
+            ('s', _) => formatted += &format!("{:02}", timestamp.second()),
+            ('f' | 'F', _) => {
+                let fraction = format!("{:09}", timestamp.nanosecond() % 1_000_000_000);
+                formatted += &fraction[..run.min(9)];
+            }
+            ('t', 1) => formatted += &timestamp.format("%p").to_string()[..1],
+            ('t', _) => formatted += &timestamp.format("%p").to_string(),
+            ('z', 1 | 2) => {
+                let hours = timestamp.offset().local_minus_utc() / 3600;
+                formatted += &format!("{hours:+0width$}", width = run + 1);
+            }
+            ('z', _) => formatted += &timestamp.format("%:z").to_string(),
+            ('K', _) if timestamp.offset().local_minus_utc() == 0 => formatted.push('Z'),
+            ('K', _) => formatted += &timestamp.format("%:z").to_string(),
+            _ => formatted.extend(std::iter::repeat_n(c, run)),
+        }
+    }
+    formatted
+}
@@ -0,0 +1,324 @@
+use std::{fmt, sync::OnceLock};
+
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Teams rejects webhook messages larger than this, whatever is in them.
+pub const MAX_MESSAGE_BYTES: usize = 28 * 1024;
+
+/// Content type of the Adaptive Card attachments of a message.
+pub const CONTENT_TYPE: &str = "application/vnd.microsoft.card.adaptive";
+
+// Every type an Adaptive Card 1.5 may contain, including the ones that only appear as children
+// of another element.
+const KNOWN_TYPES: [&str; 27] = [
+    "AdaptiveCard",
+    "TextBlock",
+    "Image",
+    "Media",
+    "RichTextBlock",
+    "TextRun",
+    "Container",
+    "ColumnSet",
+    "Column",
+    "FactSet",
+    "ImageSet",
+    "ActionSet",
+    "Table",
+    "TableRow",
+    "TableCell",
+    "Input.Text",
+    "Input.Number",
+    "Input.Date",
+    "Input.Time",
+    "Input.Toggle",
+    "Input.ChoiceSet",
+    "Action.OpenUrl",
+    "Action.Submit",
+    "Action.ShowCard",
+    "Action.ToggleVisibility",
+    "Action.Execute",
+    "Input.Choice",
+];
+
+// Valid 1.5 types that Teams can't show in a webhook message: media needs a bot to play it,
+// and Submit and Execute need a bot to receive what was submitted.
+const UNSUPPORTED_TYPES: [&str; 3] = ["Media", "Action.Submit", "Action.Execute"];
+
+// Properties holding nested elements or actions.  Others, like `data` or `msteams`, are
+// free-form and may have a `type` of their own.
+const CHILDREN: [&str; 12] = [
+    "body",
+    "items",
+    "columns",
+    "rows",
+    "cells",
+    "images",
+    "inlines",
+    "actions",
+    "card",
+    "selectAction",
+    "inlineAction",
+    "fallback",
+];
+
+/// A reason Teams would refuse, or not properly show, a message.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Violation {
+    /// The card doesn't match the bundled Adaptive Card 1.5 schema
+    Schema {
+        path: String,
+        message: String,
+    },
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    UnknownType {
+        path: String,
+        element: String,
+    },
+    Unsupported {
+        path: String,
+        element: String,
+    },
+    /// The message is for a MessageCard channel but has nothing to make one from
+    NoMessageCard {
+        reason: String,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Schema { path, message } => write!(f, "{path}: {message}"),
+            Violation::TooLarge { size, limit } => {
+                write!(f, "message is {size} bytes, Teams accepts at most {limit}")
+            }
+            Violation::UnknownType { path, element } => {
+                write!(f, "{path}: {element} is not an Adaptive Card 1.5 type")
+            }
+            Violation::Unsupported { path, element } => {
+                write!(
+                    f,
+                    "{path}: {element} is not supported in Teams webhook messages"
+                )
+            }
+            Violation::NoMessageCard { reason } => {
+                write!(f, "can't be sent as a MessageCard: {reason}")
+            }
+        }
+    }
+}
+
+/// Why a message wasn't sent: every violation found, not only the first.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CardError {
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let violations: Vec<String> = self.violations.iter().map(|v| v.to_string()).collect();
+        write!(f, "invalid message: {}", violations.join("; "))
+    }
+}
+
+impl std::error::Error for CardError {}
+
+// The Adaptive Card schema, version 1.5.0, vendored unchanged from
+// https://adaptivecards.io/schemas/1.5.0/adaptive-card.json so that upgrading it is a matter of
+// replacing the file:
+//
+//   curl -o schemas/adaptive-card-1.5.json \
+//     https://adaptivecards.io/schemas/1.5.0/adaptive-card.json
+//
+// Until the published file is checked in, the bundled one is the hand-written approximation its
+// $comment describes.  Teams' own restrictions (UNSUPPORTED_TYPES, MAX_MESSAGE_BYTES) are
+// checked in `validate`, never in the schema.
+const SCHEMA_SOURCE: &str = "https://adaptivecards.io/schemas/1.5.0/adaptive-card.json";
+
+fn schema() -> &'static Validator {
+    static SCHEMA: OnceLock<Validator> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        let schema = serde_json::from_str(include_str!("../schemas/adaptive-card-1.5.json"))
+            .unwrap_or_else(|err| panic!("the bundled {SCHEMA_SOURCE} is not JSON: {err}"));
+        jsonschema::validator_for(&schema)
+            .unwrap_or_else(|err| panic!("the bundled {SCHEMA_SOURCE} is not a schema: {err}"))
+    })
+}
+
+fn message_size(message: &Value) -> usize {
+    serde_json::to_vec(message).map_or(0, |json| json.len())
+}
+
+// JSON pointers of the Adaptive Cards attached to a message.
+fn cards(message: &Value) -> Vec<String> {
+    let Some(attachments) = message.get("attachments").and_then(Value::as_array) else {
+        return vec![];
+    };
+    attachments
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.get("contentType").and_then(Value::as_str) == Some(CONTENT_TYPE))
+        .map(|(i, _)| format!("/attachments/{i}/content"))
+        .collect()
+}
+
+// Calls `visit` with the pointer of every element and action in `value`, and descends into
+// its children when `visit` returns true.
+fn walk(value: &Value, pointer: &str, visit: &mut impl FnMut(&str, &Map<String, Value>) -> bool) {
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                walk(item, &format!("{pointer}/{i}"), visit);
+            }
+        }
+        Value::Object(object) if visit(pointer, object) => {
+            for key in CHILDREN {
+                if let Some(child) = object.get(key) {
+                    walk(child, &format!("{pointer}/{key}"), visit);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Checks a message before it is sent: each card against the schema and the types Teams
+/// supports, and the whole message against MAX_MESSAGE_BYTES.
+pub fn validate(message: &Value) -> Result<(), CardError> {
+    let mut violations = vec![];
+    for pointer in cards(message) {
+        let Some(card) = message.pointer(&pointer) else {
+            continue;
+        };
+        violations.extend(schema().iter_errors(card).map(|err| Violation::Schema {
+            path: format!("{pointer}{}", err.instance_path()),
+            message: err.masked().to_string(),
+        }));
+        walk(card, &pointer, &mut |path, object| {
+            let Some(element) = object.get("type").and_then(Value::as_str) else {
+                return true;
+            };
+            // Renderers show the fallback in place of an element they can't, so only the
+            // fallback needs checking
+            let has_fallback = object.contains_key("fallback");
+            if !KNOWN_TYPES.contains(&element) && !has_fallback {
+                violations.push(Violation::UnknownType {
+                    path: path.to_string(),
+                    element: element.to_string(),
+                });
+            } else if UNSUPPORTED_TYPES.contains(&element) && !has_fallback {
+                violations.push(Violation::Unsupported {
+                    path: path.to_string(),
+                    element: element.to_string(),
+                });
+            }
+            true
+        });
+    }
+    if let Err(err) = check_size(message) {
+        violations.extend(err.violations);
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(CardError { violations })
+    }
+}
+
+/// Checks only the size of a message against MAX_MESSAGE_BYTES, for bodies that aren't Adaptive
+/// Cards such as MessageCards.
+pub fn check_size(message: &Value) -> Result<(), CardError> {
+    let size = message_size(message);
+    if size > MAX_MESSAGE_BYTES {
+        return Err(CardError {
+            violations: vec![Violation::TooLarge {
+                size,
+                limit: MAX_MESSAGE_BYTES,
+            }],
+        });
+    }
+    Ok(())
+}
+
+enum Shortenable {
+    Text(String),
+    Facts(String),
+}
+
+/// Shortens the longest text blocks and fact lists, longest first, until the message fits in
+/// MAX_MESSAGE_BYTES.  Text is cut off with an ellipsis and dropped facts are summed up in a
+/// last "…" fact.  Returns whether anything was shortened; the message may still not fit when
+/// its size is in something else.
+pub fn truncate(message: &mut Value) -> bool {
+    let mut candidates = vec![];
+    for pointer in cards(message) {
+        if let Some(card) = message.pointer(&pointer) {
+            walk(card, &pointer, &mut |path, object| {
+                match object.get("type").and_then(Value::as_str) {
+                    Some("TextBlock") => candidates.push(Shortenable::Text(format!("{path}/text"))),
+                    Some("FactSet") => candidates.push(Shortenable::Facts(format!("{path}/facts"))),
+                    _ => {}
+                }
+                true
+            });
+        }
+    }
+    let mut dropped_facts = vec![0; candidates.len()];
+    let mut shortened = false;
+    loop {
+        let size = message_size(message);
+        if size <= MAX_MESSAGE_BYTES {
+            return shortened;
+        }
+        let excess = size - MAX_MESSAGE_BYTES;
+        let longest = candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| {
+                let (Shortenable::Text(pointer) | Shortenable::Facts(pointer)) = candidate;
+                Some((i, message_size(message.pointer(pointer)?)))
+            })
+            .max_by_key(|(_, size)| *size);
+        let Some((i, _)) = longest else {
+            return shortened;
+        };
+        let done = match &candidates[i] {
+            Shortenable::Text(pointer) => match message.pointer_mut(pointer) {
+                Some(Value::String(text)) if text.chars().count() > 1 => {
+                    let mut end = text.len().saturating_sub(excess + '…'.len_utf8());
+                    while !text.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    text.truncate(end);
+                    text.push('…');
+                    false
+                }
+                _ => true,
+            },
+            Shortenable::Facts(pointer) => match message.pointer_mut(pointer) {
+                Some(Value::Array(facts)) if facts.len() > 2 => {
+                    if dropped_facts[i] > 0 {
+                        facts.pop();
+                    }
+                    facts.pop();
+                    dropped_facts[i] += 1;
+                    let more = format!("{} more", dropped_facts[i]);
+                    facts.push(json!({"title": "…", "value": more}));
+                    false
+                }
+                _ => true,
+            },
+        };
+        if done {
+            candidates.remove(i);
+            dropped_facts.remove(i);
+        } else {
+            shortened = true;
+        }
+    }
+}